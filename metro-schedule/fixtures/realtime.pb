@@ -0,0 +1,19 @@
+
+
+2.0)
+1$
+	
+R1*RED	"CWE
+2
+
+R3 *RED
+3
+	
+B1*RED(y
+4*t
+*
+METROREDRV
+*
+$Trenes de la línea roja con retrasoes
+(
+"Red Line trains delayed near Granden
\ No newline at end of file
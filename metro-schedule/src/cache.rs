@@ -0,0 +1,176 @@
+use crate::{Schedules, Source};
+use chrono::{DateTime, Local};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Records when the cached tables were scraped, as RFC 3339.
+const FETCHED_AT: &str = "fetched_at";
+
+/// Something [`Schedules::load_or_fetch`] worked around, for the caller to report.
+#[derive(Debug)]
+pub enum Fallback {
+    /// Fresh schedules were fetched but couldn't be written to the cache.
+    NotCached(io::Error),
+    /// Fetching failed, for this reason, so the cached schedules are used instead.
+    Cached(anyhow::Error),
+}
+
+impl Schedules {
+    pub(crate) fn tables(&self) -> [(&'static str, &String); 4] {
+        [
+            ("weekday_west.csv", &self.weekday_west),
+            ("weekday_east.csv", &self.weekday_east),
+            ("weekend_west.csv", &self.weekend_west),
+            ("weekend_east.csv", &self.weekend_east),
+        ]
+    }
+
+    /// Writes the filtered tables to `dir`, creating it if needed. Each file goes to a temp
+    /// file first and is renamed into place so a reader never sees half a table.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let fetched_at = self.fetched_at.to_rfc3339();
+        for (name, contents) in self.tables().into_iter().chain([(FETCHED_AT, &fetched_at)]) {
+            let path = dir.join(name);
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, contents)?;
+            fs::rename(tmp, path)?;
+        }
+        Ok(())
+    }
+
+    /// Reads back tables written by [`Schedules::save`].
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let read = |name: &str| fs::read_to_string(dir.join(name));
+        let fetched_at = DateTime::parse_from_rfc3339(read(FETCHED_AT)?.trim())
+            .map_err(io::Error::other)?
+            .with_timezone(&Local);
+        Ok(Self {
+            weekday_west: read("weekday_west.csv")?,
+            weekday_east: read("weekday_east.csv")?,
+            weekend_west: read("weekend_west.csv")?,
+            weekend_east: read("weekend_east.csv")?,
+            fetched_at,
+        })
+    }
+
+    /// Fetches fresh schedules from `source` unless `offline`, keeping a copy in `cache`. Falls back to the
+    /// cached copy when the site can't be reached or sends something that doesn't validate,
+    /// so an outage at boot still leaves the last known timetable. Also returns whatever it
+    /// had to fall back from.
+    pub async fn load_or_fetch(
+        source: &Source,
+        cache: Option<&Path>,
+        offline: bool,
+    ) -> anyhow::Result<(Self, Option<Fallback>)> {
+        let fetched = if offline {
+            Err(anyhow::anyhow!("fetching is disabled"))
+        } else {
+            source
+                .fetch()
+                .await
+                .and_then(|schedules| schedules.validate().map(|_| schedules))
+        };
+        match (fetched, cache) {
+            (Ok(schedules), Some(dir)) => {
+                let fallback = schedules.save(dir).err().map(Fallback::NotCached);
+                Ok((schedules, fallback))
+            }
+            (Ok(schedules), None) => Ok((schedules, None)),
+            (Err(e), Some(dir)) => {
+                let schedules = Self::load(dir).map_err(|cache_error| {
+                    anyhow::anyhow!("{e}; no cached schedules either: {cache_error}")
+                })?;
+                Ok((schedules, Some(Fallback::Cached(e))))
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("metro-{name}-{}", std::process::id()))
+    }
+
+    fn schedules() -> Schedules {
+        Schedules {
+            weekday_west: "ShilohScott Station\n5:01AR\n".to_string(),
+            weekday_east: "Lambert Airport Trmnl #1 Station\n4:50AR\n".to_string(),
+            weekend_west: String::new(),
+            weekend_east: String::new(),
+            fetched_at: DateTime::parse_from_rfc3339("2025-03-01T06:00:00-06:00")
+                .unwrap()
+                .with_timezone(&Local),
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_dir("saved");
+        let schedules = schedules();
+        schedules.save(&dir).unwrap();
+        let loaded = Schedules::load(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.tables(), schedules.tables());
+        assert_eq!(loaded.fetched_at, schedules.fetched_at);
+        assert!(Schedules::load(&dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_uses_cache() {
+        let dir = temp_dir("fallback");
+        let schedules = schedules();
+        schedules.save(&dir).unwrap();
+
+        let missing = Source::Gtfs("fixtures/missing.zip".to_string());
+        let (loaded, fallback) = Schedules::load_or_fetch(&missing, Some(&dir), false)
+            .await
+            .unwrap();
+        assert!(matches!(fallback, Some(Fallback::Cached(_))));
+        assert_eq!(loaded.tables(), schedules.tables());
+        // offline never tries the source at all
+        let (_, fallback) = Schedules::load_or_fetch(&Source::Scrape, Some(&dir), true)
+            .await
+            .unwrap();
+        assert!(matches!(fallback, Some(Fallback::Cached(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(Schedules::load_or_fetch(&missing, Some(&dir), false)
+            .await
+            .is_err());
+        assert!(Schedules::load_or_fetch(&missing, None, false)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_updates_cache() {
+        let dir = temp_dir("fetched");
+        let feed = Source::Gtfs("fixtures/gtfs.zip".to_string());
+        let (fetched, fallback) = Schedules::load_or_fetch(&feed, Some(&dir), false)
+            .await
+            .unwrap();
+        assert!(fallback.is_none());
+        assert_eq!(Schedules::load(&dir).unwrap().tables(), fetched.tables());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (_, fallback) = Schedules::load_or_fetch(&feed, None, false).await.unwrap();
+        assert!(fallback.is_none());
+
+        // a file in the way of the cache directory still gives the fresh schedules
+        let blocked = temp_dir("blocked");
+        fs::write(&blocked, "").unwrap();
+        let (schedules, fallback) = Schedules::load_or_fetch(&feed, Some(&blocked), false)
+            .await
+            .unwrap();
+        fs::remove_file(&blocked).unwrap();
+        assert!(matches!(fallback, Some(Fallback::NotCached(_))));
+        assert_eq!(schedules.tables(), fetched.tables());
+    }
+}
@@ -0,0 +1,504 @@
+use crate::Schedules;
+use anyhow::Context;
+use chrono::{Datelike, Local, NaiveDate, Weekday};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use zip::ZipArchive;
+
+const NUM_COLUMNS: usize = 38;
+
+/// Column headers of the scraped timetables, west to east. The GTFS tables use the same ones
+/// so both sources read alike.
+const COLUMNS: [&str; NUM_COLUMNS] = [
+    "Lambert Airport Trmnl #1 Station",
+    "Lambert Airport Trmnl #2 Station",
+    "North Hanley Station",
+    "UMSL North Station",
+    "UMSL South Station",
+    "Rock Road Station",
+    "Wellston Station",
+    "Delmar Loop Station",
+    "ShrewsburyLansdowne I44 Station",
+    "Sunnen Station",
+    "MaplewoodManchester Station",
+    "Brentwood I64 Station",
+    "Richmond Heights Station",
+    "Clayton Station",
+    "Forsyth Station",
+    "University CityBig Bend Station",
+    "Skinker Station",
+    "Forest ParkDeBaliviere Station",
+    "Central West End Station",
+    "Cortex Station",
+    "Grand Station",
+    "Union Station",
+    "Civic Center Station",
+    "Stadium Station",
+    "8th & Pine (Temporarily Closed)",
+    "Convention Center Station",
+    "Laclede's Landing Station",
+    "East Riverfront Station",
+    "5th & Missouri Station",
+    "Emerson Park Station",
+    "JJK Center Station",
+    "Washington Park Station",
+    "Fairview Heights Station",
+    "Memorial Hospital Station",
+    "Swansea Station",
+    "Belleville Station",
+    "College Station",
+    "ShilohScott Station",
+];
+
+#[derive(Deserialize)]
+struct Stop {
+    stop_id: String,
+    stop_name: String,
+}
+
+#[derive(Deserialize)]
+struct Route {
+    route_id: String,
+    #[serde(default)]
+    route_short_name: Option<String>,
+    #[serde(default)]
+    route_long_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Trip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+}
+
+#[derive(Deserialize)]
+struct StopTime {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: Option<String>,
+    #[serde(default)]
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+#[derive(Deserialize)]
+struct Calendar {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+}
+
+impl Calendar {
+    fn runs_on(&self, weekend: bool) -> bool {
+        if weekend {
+            [self.saturday, self.sunday].contains(&1)
+        } else {
+            [
+                self.monday,
+                self.tuesday,
+                self.wednesday,
+                self.thursday,
+                self.friday,
+            ]
+            .contains(&1)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarDate {
+    service_id: String,
+    date: String,
+    /// 1 adds the service on `date`, 2 removes it.
+    exception_type: u8,
+}
+
+/// The parts of a feed that go into the timetables.
+struct Feed {
+    /// Which column each MetroLink stop fills.
+    columns: HashMap<String, usize>,
+    /// The line letter, `R` or `B`, for each MetroLink route.
+    lines: HashMap<String, char>,
+    /// MetroLink trips only.
+    trips: Vec<Trip>,
+    stop_times: HashMap<String, Vec<StopTime>>,
+    calendar: Vec<Calendar>,
+    calendar_dates: Vec<CalendarDate>,
+}
+
+impl Schedules {
+    /// Reads a GTFS static zip. The weekday and weekend tables follow the days of the week in
+    /// the service calendar, leaving out one-off exceptions like holidays, so the same feed
+    /// always gives the same tables whatever day it is read on.
+    pub fn from_gtfs(zip: &[u8]) -> anyhow::Result<Self> {
+        let feed = Feed::read(zip)?;
+        let (weekday_east, weekday_west) = feed.tables(false)?;
+        let (weekend_east, weekend_west) = feed.tables(true)?;
+        Ok(Self {
+            weekday_west,
+            weekday_east,
+            weekend_west,
+            weekend_east,
+            fetched_at: Local::now(),
+        })
+    }
+}
+
+impl Feed {
+    fn read(zip: &[u8]) -> anyhow::Result<Self> {
+        let mut archive = ZipArchive::new(Cursor::new(zip)).context("not a zip file")?;
+
+        let mut columns = HashMap::new();
+        for stop in read::<Stop>(&mut archive, "stops.txt")? {
+            if let Some(column) = column(&stop.stop_name) {
+                columns.insert(stop.stop_id, column);
+            }
+        }
+        let lines = if archive.index_for_name("routes.txt").is_some() {
+            read::<Route>(&mut archive, "routes.txt")?
+                .into_iter()
+                .filter_map(|route| {
+                    let name = [&route.route_short_name, &route.route_long_name]
+                        .into_iter()
+                        .flatten()
+                        .fold(route.route_id.clone(), |names, name| names + " " + name);
+                    line(&name).map(|line| (route.route_id, line))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let trips = read_where(&mut archive, "trips.txt", |trip: &Trip| {
+            lines.contains_key(&trip.route_id)
+        })?;
+        // stop_times.txt is by far the biggest file and mostly buses, so only the MetroLink
+        // rows are kept as it is read
+        let metro_trips: HashSet<&str> = trips.iter().map(|trip| trip.trip_id.as_str()).collect();
+        let mut stop_times: HashMap<String, Vec<StopTime>> = HashMap::new();
+        for stop_time in read_where(&mut archive, "stop_times.txt", |stop_time: &StopTime| {
+            metro_trips.contains(stop_time.trip_id.as_str())
+        })? {
+            stop_times
+                .entry(stop_time.trip_id.clone())
+                .or_default()
+                .push(stop_time);
+        }
+        for times in stop_times.values_mut() {
+            times.sort_by_key(|stop_time| stop_time.stop_sequence);
+        }
+        // a feed can describe its service with either calendar file alone
+        let calendar = match archive.index_for_name("calendar.txt") {
+            Some(_) => read(&mut archive, "calendar.txt")?,
+            None => Vec::new(),
+        };
+        let calendar_dates = match archive.index_for_name("calendar_dates.txt") {
+            Some(_) => read(&mut archive, "calendar_dates.txt")?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            columns,
+            lines,
+            trips,
+            stop_times,
+            calendar,
+            calendar_dates,
+        })
+    }
+
+    /// Whether `service_id` runs on weekends or on weekdays. A service that only appears in
+    /// `calendar_dates.txt` counts for whichever kind of day most of its dates fall on.
+    fn runs_on(&self, service_id: &str, weekend: bool) -> bool {
+        let mut calendars = self
+            .calendar
+            .iter()
+            .filter(|calendar| calendar.service_id == service_id)
+            .peekable();
+        if calendars.peek().is_some() {
+            return calendars.any(|calendar| calendar.runs_on(weekend));
+        }
+        let (weekends, weekdays) = self
+            .calendar_dates
+            .iter()
+            .filter(|date| date.service_id == service_id && date.exception_type == 1)
+            .filter_map(|date| NaiveDate::parse_from_str(&date.date, "%Y%m%d").ok())
+            .partition::<Vec<_>, _>(|date| matches!(date.weekday(), Weekday::Sat | Weekday::Sun));
+        if weekend {
+            weekends.len() > weekdays.len()
+        } else {
+            weekdays.len() >= weekends.len() && !weekdays.is_empty()
+        }
+    }
+
+    /// The eastbound and westbound tables for weekends or weekdays, one row per train in order
+    /// of departure.
+    fn tables(&self, weekend: bool) -> anyhow::Result<(String, String)> {
+        let mut east = Vec::new();
+        let mut west = Vec::new();
+        for trip in &self.trips {
+            let Some(line) = self.lines.get(&trip.route_id) else {
+                continue;
+            };
+            if !self.runs_on(&trip.service_id, weekend) {
+                continue;
+            }
+            let stops: Vec<(usize, Option<u32>)> = self
+                .stop_times
+                .get(&trip.trip_id)
+                .into_iter()
+                .flatten()
+                .filter_map(|stop_time| {
+                    let column = *self.columns.get(&stop_time.stop_id)?;
+                    let time = stop_time
+                        .departure_time
+                        .as_deref()
+                        .or(stop_time.arrival_time.as_deref())
+                        .and_then(seconds);
+                    Some((column, time))
+                })
+                .collect();
+            let (Some((first, _)), Some((last, _))) = (stops.first(), stops.last()) else {
+                continue;
+            };
+            if first == last {
+                continue;
+            }
+            let mut row = [None; NUM_COLUMNS];
+            for (column, time) in &stops {
+                row[*column] = time.map(|time| (time, *line));
+            }
+            let departs = stops.iter().find_map(|(_, time)| *time).unwrap_or_default();
+            let train = Train {
+                departs,
+                trip: &trip.trip_id,
+                route: &trip.route_id,
+                row,
+            };
+            if last > first {
+                east.push(train);
+            } else {
+                west.push(train);
+            }
+        }
+        Ok((table(east, false)?, table(west, true)?))
+    }
+}
+
+fn read<T: DeserializeOwned>(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> anyhow::Result<Vec<T>> {
+    read_where(archive, name, |_| true)
+}
+
+/// The rows of `name` that `keep` accepts, read straight out of the zip so rows that aren't
+/// kept never take up memory. A leading byte order mark is skipped by the csv reader.
+fn read_where<T: DeserializeOwned>(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+    mut keep: impl FnMut(&T) -> bool,
+) -> anyhow::Result<Vec<T>> {
+    let file = archive
+        .by_name(name)
+        .with_context(|| format!("feed has no {name}"))?;
+    let mut rows = Vec::new();
+    for row in csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file)
+        .into_deserialize()
+    {
+        let row = row.with_context(|| format!("can't parse {name}"))?;
+        if keep(&row) {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+/// The timetable column a stop belongs in, matching names like `Forest Park-DeBaliviere
+/// MetroLink Station` against `Forest ParkDeBaliviere Station`.
+fn column(stop_name: &str) -> Option<usize> {
+    let stop = normalize(stop_name);
+    COLUMNS.iter().position(|column| normalize(column) == stop)
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.to_lowercase();
+    let name = name.split('(').next().unwrap_or_default();
+    name.replace("terminal", "trmnl")
+        .replace("metrolink", "")
+        .replace("station", "")
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// `R` or `B`, the letter the scraped tables mark each line's trains with.
+fn line(route_name: &str) -> Option<char> {
+    let name = route_name.to_lowercase();
+    if name.contains("red") {
+        Some('R')
+    } else if name.contains("blue") {
+        Some('B')
+    } else {
+        None
+    }
+}
+
+/// Seconds past midnight for a GTFS `HH:MM:SS`, which runs past 24:00:00 for trains after
+/// midnight.
+fn seconds(time: &str) -> Option<u32> {
+    let mut parts = time.split(':').map(|part| part.parse::<u32>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Writes time like the scraped tables do: `5:08AR` is 5:08 in the morning on the Red line.
+fn format_time(seconds: u32, line: char) -> String {
+    let hour = seconds / 3600 % 24;
+    let minute = seconds / 60 % 60;
+    let half = if hour < 12 { 'A' } else { 'P' };
+    let hour = match hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    format!("{hour}:{minute:02}{half}{line}")
+}
+
+/// One trip's row of the timetable.
+struct Train<'f> {
+    /// When it leaves its first station, for putting the rows in order.
+    departs: u32,
+    trip: &'f str,
+    route: &'f str,
+    /// Time and line letter at each station it stops at.
+    row: [Option<(u32, char)>; NUM_COLUMNS],
+}
+
+/// A CSV table of `trains` with a column for every station any of them stops at, in the
+/// order the trains reach them. Each row also names its GTFS trip and route so live updates
+/// can be matched to it.
+fn table(mut trains: Vec<Train>, westbound: bool) -> anyhow::Result<String> {
+    trains.sort_by_key(|train| train.departs);
+    let mut columns: Vec<usize> = (0..NUM_COLUMNS)
+        .filter(|column| trains.iter().any(|train| train.row[*column].is_some()))
+        .collect();
+    if westbound {
+        columns.reverse();
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(
+        ["Trip", "Route"]
+            .into_iter()
+            .chain(columns.iter().map(|column| COLUMNS[*column])),
+    )?;
+    for train in &trains {
+        let times = columns.iter().map(|column| {
+            train.row[*column]
+                .map(|(time, line)| format_time(time, line))
+                .unwrap_or_default()
+        });
+        writer.write_record(
+            [train.trip.to_string(), train.route.to_string()]
+                .into_iter()
+                .chain(times),
+        )?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FEED: &[u8] = include_bytes!("../fixtures/gtfs.zip");
+
+    #[test]
+    fn test_weekday_tables() {
+        let schedules = Schedules::from_gtfs(FEED).unwrap();
+        schedules.validate().unwrap();
+
+        assert_eq!(
+            schedules.weekday_east,
+            "Trip,Route,Forest ParkDeBaliviere Station,Central West End Station,Cortex Station,Grand Station\n\
+             R1,RED,5:00AR,5:03AR,5:05AR,5:08AR\n\
+             B1,BLUE,1:15PB,,1:20PB,1:23PB\n\
+             R3,RED,12:30AR,12:33AR,12:35AR,12:38AR\n"
+        );
+        assert_eq!(
+            schedules.weekday_west,
+            "Trip,Route,Grand Station,Cortex Station,Central West End Station,Forest ParkDeBaliviere Station\n\
+             R2,RED,5:30AR,5:33AR,5:35AR,5:38AR\n"
+        );
+        // the bus doesn't make it into either table, and only the weekend trains run Saturday
+        assert_eq!(
+            schedules.weekend_east,
+            "Trip,Route,Forest ParkDeBaliviere Station,Grand Station\nW1,RED,8:00AR,8:08AR\n"
+        );
+    }
+
+    #[test]
+    fn test_holiday_service() {
+        // Memorial Day runs the weekend schedule, which mustn't leak into the regular tables
+        let schedules = Schedules::from_gtfs(FEED).unwrap();
+        assert_ne!(schedules.weekday_east, schedules.weekend_east);
+        assert_eq!(
+            schedules.weekend_west,
+            "Trip,Route,Grand Station,Forest ParkDeBaliviere Station\nW2,RED,9:00AR,9:08AR\n"
+        );
+        // and bus trips never get as far as their stop times
+        let feed = Feed::read(FEED).unwrap();
+        assert!(feed.trips.iter().all(|trip| trip.route_id != "95"));
+        assert!(!feed.stop_times.contains_key("BUS1"));
+    }
+
+    #[test]
+    fn test_calendar_dates_only() {
+        let added = |service_id: &str, date: &str| CalendarDate {
+            service_id: service_id.to_string(),
+            date: date.to_string(),
+            exception_type: 1,
+        };
+        let feed = Feed {
+            columns: HashMap::new(),
+            lines: HashMap::new(),
+            trips: Vec::new(),
+            stop_times: HashMap::new(),
+            calendar: Vec::new(),
+            // a weekend service that also runs on Memorial Day
+            calendar_dates: vec![
+                added("WKND", "20250524"),
+                added("WKND", "20250525"),
+                added("WKND", "20250526"),
+                added("WKDY", "20250527"),
+            ],
+        };
+        assert!(feed.runs_on("WKND", true));
+        assert!(!feed.runs_on("WKND", false));
+        assert!(feed.runs_on("WKDY", false));
+        assert!(!feed.runs_on("WKDY", true));
+        assert!(!feed.runs_on("NONE", false));
+    }
+
+    #[test]
+    fn test_stop_names() {
+        assert_eq!(
+            column("Lambert Airport Terminal #1 MetroLink Station"),
+            Some(0)
+        );
+        assert_eq!(column("8th & Pine MetroLink Station"), Some(24));
+        assert_eq!(column("Grand @ Lindell"), None);
+        assert_eq!(format_time(0, 'B'), "12:00AB");
+        assert_eq!(seconds("25:10:00"), Some(90600));
+    }
+}
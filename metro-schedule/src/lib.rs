@@ -1,21 +1,66 @@
-use chrono::{DateTime, Datelike, Local, Weekday};
+use chrono::{DateTime, Datelike, Local, TimeDelta, Weekday};
 use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+mod cache;
+mod gtfs;
+mod realtime;
+pub mod refresh;
+
+pub use cache::Fallback;
+pub use realtime::{LiveFeed, Realtime};
+
+/// Schedules older than this get a warning in arrival responses.
+const STALE_AFTER: TimeDelta = TimeDelta::days(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct NextArrivalRequest {
     pub station: Station,
     pub direction: Direction,
+    /// How many upcoming departures to list.
+    #[serde(default = "one")]
+    pub count: usize,
+    /// Only list trains on this line.
+    #[serde(default)]
+    pub line: Option<Line>,
+}
+
+fn one() -> usize {
+    1
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct NextArrivalResponse {
     pub station: Station,
     pub direction: Direction,
+    /// The next trains to leave, soonest first.
+    pub departures: Vec<Departure>,
+    /// When the schedule the times came from was scraped.
+    #[serde(default)]
+    pub fetched_at: Option<DateTime<Local>>,
+    /// Service alerts for the listed trains' lines.
+    #[serde(default)]
+    pub alerts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Departure {
     pub line: String,
+    /// The timetabled time, like `5:08A`.
     pub time: String,
+    /// Minutes from now until the train leaves, going by live data when there is some.
+    pub minutes: i64,
+    /// When the train is now expected, going by live data.
+    #[serde(default)]
+    pub predicted: Option<String>,
+    /// How far behind the timetable the train is running, negative when early.
+    #[serde(default)]
+    pub delay_minutes: Option<i64>,
+    /// Whether live data backs up the time, rather than the timetable alone.
+    #[serde(default)]
+    pub realtime: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -96,18 +141,97 @@ pub struct StationTimeSlice {
     pub college: Option<String>,
     #[serde(rename = "ShilohScott Station")]
     pub shiloh_scott: Option<String>,
+    /// GTFS trip id, in tables built from a GTFS feed.
+    #[serde(rename = "Trip")]
+    pub trip: Option<String>,
+    /// GTFS route id, in tables built from a GTFS feed.
+    #[serde(rename = "Route")]
+    pub route: Option<String>,
+}
+
+impl StationTimeSlice {
+    /// Every time in this row, skipping stations the train doesn't stop at.
+    pub fn times(&self) -> impl Iterator<Item = &String> {
+        [
+            &self.lambert_t1,
+            &self.lambert_t2,
+            &self.north_hanley,
+            &self.umsl_north,
+            &self.umsl_south,
+            &self.rock_road,
+            &self.wellston,
+            &self.delmar_loop,
+            &self.shrewsbury,
+            &self.sunnen,
+            &self.maplewood_manchester,
+            &self.brentwood,
+            &self.richmond_heights,
+            &self.clayton,
+            &self.forsyth,
+            &self.u_city,
+            &self.skinker,
+            &self.forest_park,
+            &self.cwe,
+            &self.cortex,
+            &self.grand,
+            &self.union,
+            &self.civic_center,
+            &self.stadium,
+            &self.eight_pine,
+            &self.convention_center,
+            &self.lacledes_landing,
+            &self.east_riverfront,
+            &self.fifth_missouri,
+            &self.emerson_park,
+            &self.jjk,
+            &self.washington,
+            &self.fairview_heights,
+            &self.memorial_hospital,
+            &self.swansea,
+            &self.belleville,
+            &self.college,
+            &self.shiloh_scott,
+        ]
+        .into_iter()
+        .flatten()
+    }
 }
 
 impl Display for NextArrivalResponse {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
             f,
-            r#"station: {}
-direction: {}
-line: {}
-time: {}"#,
-            self.station, self.direction, self.line, self.time
-        )
+            "station: {}\ndirection: {}",
+            self.station, self.direction
+        )?;
+        for departure in &self.departures {
+            write!(f, "\n{departure}")?;
+        }
+        for alert in &self.alerts {
+            write!(f, "\nalert: {alert}")?;
+        }
+        match self.fetched_at {
+            Some(fetched_at) if Local::now() - fetched_at > STALE_AFTER => write!(
+                f,
+                "\nschedule from {}, may be out of date",
+                fetched_at.format("%b %-d")
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for Departure {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {} in {} min", self.time, self.line, self.minutes)?;
+        if let (Some(predicted), Some(delay)) = (&self.predicted, self.delay_minutes) {
+            match delay {
+                0 => write!(f, " (on time)")?,
+                delay if delay > 0 => write!(f, " (expected {predicted}, {delay} min late)")?,
+                delay => write!(f, " (expected {predicted}, {} min early)", -delay)?,
+            }
+        }
+        Ok(())
     }
 }
 
@@ -198,6 +322,22 @@ impl Display for Station {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Line {
+    Red,
+    Blue,
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Red => write!(f, "red"),
+            Self::Blue => write!(f, "blue"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Direction {
     East,
@@ -219,6 +359,7 @@ pub struct Schedules {
     pub weekday_east: String,
     pub weekend_west: String,
     pub weekend_east: String,
+    pub fetched_at: DateTime<Local>,
 }
 
 impl Schedules {
@@ -285,6 +426,40 @@ impl Schedules {
             weekday_east: Self::filter_content(weekday_east?),
             weekend_west: Self::filter_content(weekend_west?),
             weekend_east: Self::filter_content(weekend_east?),
+            fetched_at: Local::now(),
         })
     }
 }
+
+/// Where the timetables come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// The tables on metrostlouis.org.
+    Scrape,
+    /// A GTFS static zip, by URL or file path.
+    Gtfs(String),
+}
+
+impl Source {
+    pub async fn fetch(&self) -> Result<Schedules, anyhow::Error> {
+        match self {
+            Self::Scrape => Schedules::new().await,
+            Self::Gtfs(location) => Schedules::from_gtfs(&read_location(location).await?),
+        }
+    }
+}
+
+/// Downloads `location` if it is a URL, or reads it as a file otherwise, which lets tests and
+/// offline setups point at a saved copy.
+pub(crate) async fn read_location(location: &str) -> Result<Vec<u8>, anyhow::Error> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        Ok(reqwest::get(location)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec())
+    } else {
+        Ok(tokio::fs::read(location).await?)
+    }
+}
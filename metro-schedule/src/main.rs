@@ -5,24 +5,120 @@ use axum::{
     routing::post,
     Json, Router,
 };
-use chrono::{DateTime, Local, Timelike};
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, Timelike};
 use clap::{arg, command};
 use csv::Reader;
 use metro_schedule::{
-    NextArrivalRequest, NextArrivalResponse, Schedules, Station, StationTimeSlice,
+    refresh, Departure, Fallback, Line, LiveFeed, NextArrivalRequest, NextArrivalResponse,
+    Realtime, Schedules, Source, Station, StationTimeSlice,
 };
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The most departures one request can ask for.
+const MAX_DEPARTURES: usize = 10;
+
+#[derive(Clone)]
+struct AppState {
+    schedules: Arc<RwLock<Schedules>>,
+    /// The GTFS-realtime feed as last polled.
+    realtime: LiveFeed,
+}
 
 #[tokio::main]
 async fn main() {
     let cmd = command!()
         .arg(arg!( -p --port [port] "port number for webserver").required(false))
+        .arg(arg!(--cache [dir] "directory to keep the last fetched schedules in").required(false))
+        .arg(arg!(--offline "use the cached schedules instead of fetching new ones"))
+        .arg(
+            arg!(--gtfs [feed] "GTFS static zip, as a url or path, to read schedules from instead of the Metro site")
+                .required(false),
+        )
+        .arg(
+            arg!(--realtime [feed] "GTFS-realtime trip updates and alerts, as a url or path")
+                .required(false)
+                // delays are matched to departures by the GTFS trip ids
+                .requires("gtfs"),
+        )
+        .arg(
+            arg!(--"refresh-hours" [hours] "how often to fetch the schedules again")
+                .required(false)
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            arg!(--notifications [url] "notification-service url to announce timetable changes to")
+                .required(false),
+        )
         .get_matches();
     let default_port = "8000".to_string();
     let port = cmd.get_one::<String>("port").unwrap_or(&default_port);
+    let default_cache = "metro-cache".to_string();
+    let cache = PathBuf::from(cmd.get_one::<String>("cache").unwrap_or(&default_cache));
+
+    let offline = cmd.get_flag("offline");
+    let refresh_hours = cmd.get_one::<u64>("refresh-hours").copied().unwrap_or(24);
+
+    let source = cmd
+        .get_one::<String>("gtfs")
+        .map_or(Source::Scrape, |feed| Source::Gtfs(feed.clone()));
 
-    let appstate = Schedules::new()
+    let (schedules, fallback) = Schedules::load_or_fetch(&source, Some(&cache), offline)
         .await
         .expect("failed to retrieve schedule information");
+    match fallback {
+        Some(Fallback::NotCached(e)) => {
+            eprintln!("couldn't cache schedules in {}: {e}", cache.display())
+        }
+        Some(Fallback::Cached(e)) => eprintln!(
+            "using schedules cached {} ({e})",
+            schedules.fetched_at.format("%Y-%m-%d %H:%M")
+        ),
+        None => {}
+    }
+    let appstate = AppState {
+        schedules: Arc::new(RwLock::new(schedules)),
+        realtime: LiveFeed::default(),
+    };
+    if let Some(feed) = cmd.get_one::<String>("realtime").cloned() {
+        let live = appstate.realtime.clone();
+        tokio::spawn(async move {
+            let mut failing = false;
+            loop {
+                match Realtime::fetch(&feed).await {
+                    Ok(realtime) => {
+                        live.update(realtime);
+                        failing = false;
+                    }
+                    // once per outage rather than every poll
+                    Err(e) if !failing => {
+                        eprintln!("couldn't fetch the realtime feed: {e:#}");
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+                tokio::time::sleep(LiveFeed::POLL_INTERVAL).await;
+            }
+        });
+    }
+    if !offline {
+        let schedules = appstate.schedules.clone();
+        let interval = Duration::from_secs(refresh_hours * 60 * 60);
+        let notifications = cmd
+            .get_one::<String>("notifications")
+            .map(|base_url| format!("{base_url}/notifications"));
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let refreshed =
+                    refresh::refresh(&schedules, &source, Some(&cache), notifications.as_deref());
+                if let Err(e) = refreshed.await {
+                    eprintln!("{e:#}");
+                }
+            }
+        });
+    }
 
     let app = Router::new()
         .route("/next-arrival", post(next_arrival))
@@ -36,165 +132,244 @@ async fn main() {
 }
 
 async fn next_arrival(
-    State(state): State<Schedules>,
+    State(state): State<AppState>,
     Json(req): Json<NextArrivalRequest>,
 ) -> Response {
     let t = Local::now();
-    let schedule = state.choose_data_for_request(t, &req.direction);
-
-    if let Some(s) = find_next_arrival(schedule.as_ref(), &req.station, t) {
-        Json(NextArrivalResponse {
-            station: req.station,
-            direction: req.direction,
-            line: s.1,
-            time: s.0,
-        })
-        .into_response()
-    } else {
+    // without live data the timetable still answers
+    let realtime = state.realtime.current();
+    let Ok((schedule, fetched_at)) = state.schedules.read().map(|schedules| {
         (
+            schedules.choose_data_for_request(t, &req.direction),
+            schedules.fetched_at,
+        )
+    }) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let scheduled = find_departures(
+        schedule.as_ref(),
+        &req.station,
+        t,
+        realtime.as_deref(),
+        req.line,
+        req.count.clamp(1, MAX_DEPARTURES),
+    );
+    if scheduled.is_empty() {
+        return (
             StatusCode::NOT_FOUND,
             "There are no arrival times for this station",
         )
-            .into_response()
+            .into_response();
     }
+
+    let mut alerts: Vec<String> = Vec::new();
+    let mut departures = Vec::new();
+    for departure in scheduled {
+        let trip = departure.trip.as_deref();
+        let delay = realtime.as_ref().and_then(|realtime| realtime.delay(trip));
+        let Some(minutes) = minutes_until(&departure.time, delay.unwrap_or_default(), t) else {
+            eprintln!("can't read the timetable time {:?}", departure.time);
+            continue;
+        };
+        if let Some(realtime) = &realtime {
+            for alert in realtime.alerts(trip, departure.route.as_deref(), t) {
+                if !alerts.contains(&alert) {
+                    alerts.push(alert);
+                }
+            }
+        }
+        departures.push(Departure {
+            minutes,
+            predicted: delay.and_then(|delay| predicted_time(&departure.time, delay)),
+            delay_minutes: delay.map(|delay| i64::from(delay + 30).div_euclid(60)),
+            realtime: delay.is_some(),
+            line: departure.line,
+            time: departure.time,
+        });
+    }
+    Json(NextArrivalResponse {
+        station: req.station,
+        direction: req.direction,
+        departures,
+        fetched_at: Some(fetched_at),
+        alerts,
+    })
+    .into_response()
+}
+
+/// A train leaving the requested station, as the timetable has it.
+struct Scheduled {
+    time: String,
+    line: String,
+    trip: Option<String>,
+    route: Option<String>,
 }
 
 macro_rules! search_station {
-    ($s:ident, $reader:expr, $t:expr) => {
+    ($s:ident, $reader:expr, $t:expr, $realtime:expr, $line:expr, $count:expr) => {
+        let mut found = Vec::new();
         for result in $reader.deserialize() {
             if let Ok(record) = result {
                 let record: StationTimeSlice = record;
                 if let Some(s) = record.$s {
-                    if schedule_time_is_later_than_now($t, s.clone()) {
-                        return Some(line_info(s));
+                    let trip = record.trip.as_deref();
+                    if $realtime.is_some_and(|realtime| realtime.is_canceled(trip)) {
+                        continue;
+                    }
+                    // a late train can still be caught after its timetabled time
+                    let delay = $realtime
+                        .and_then(|realtime| realtime.delay(trip))
+                        .unwrap_or_default();
+                    if schedule_time_is_later_than_now(
+                        $t - TimeDelta::seconds(delay.into()),
+                        s.clone(),
+                    ) {
+                        let (time, line) = line_info(s);
+                        if $line.is_some_and(|wanted: Line| wanted.to_string() != line) {
+                            continue;
+                        }
+                        found.push(Scheduled {
+                            time,
+                            line,
+                            trip: record.trip,
+                            route: record.route,
+                        });
+                        if found.len() == $count {
+                            break;
+                        }
                     }
                 }
             }
         }
-        return None
+        return found
     };
 }
 
-fn find_next_arrival(
+/// The next `count` trains leaving `station` after `t`, optionally only those on `line`.
+fn find_departures(
     file_contents: &[u8],
     station: &Station,
     t: DateTime<Local>,
-) -> Option<(String, String)> {
+    realtime: Option<&Realtime>,
+    line: Option<Line>,
+    count: usize,
+) -> Vec<Scheduled> {
     let mut reader = Reader::from_reader(file_contents);
     match station {
         Station::LambertT1 => {
-            search_station!(lambert_t1, reader, t);
+            search_station!(lambert_t1, reader, t, realtime, line, count);
         }
         Station::LambertT2 => {
-            search_station!(lambert_t2, reader, t);
+            search_station!(lambert_t2, reader, t, realtime, line, count);
         }
         Station::NorthHanley => {
-            search_station!(north_hanley, reader, t);
+            search_station!(north_hanley, reader, t, realtime, line, count);
         }
         Station::UMSLNorth => {
-            search_station!(umsl_north, reader, t);
+            search_station!(umsl_north, reader, t, realtime, line, count);
         }
         Station::UMSLSouth => {
-            search_station!(umsl_south, reader, t);
+            search_station!(umsl_south, reader, t, realtime, line, count);
         }
         Station::RockRoad => {
-            search_station!(rock_road, reader, t);
+            search_station!(rock_road, reader, t, realtime, line, count);
         }
         Station::Wellston => {
-            search_station!(wellston, reader, t);
+            search_station!(wellston, reader, t, realtime, line, count);
         }
         Station::DelmarLoop => {
-            search_station!(delmar_loop, reader, t);
+            search_station!(delmar_loop, reader, t, realtime, line, count);
         }
         Station::Shrewsbury => {
-            search_station!(shrewsbury, reader, t);
+            search_station!(shrewsbury, reader, t, realtime, line, count);
         }
         Station::Sunnen => {
-            search_station!(sunnen, reader, t);
+            search_station!(sunnen, reader, t, realtime, line, count);
         }
         Station::MaplewoodManchester => {
-            search_station!(maplewood_manchester, reader, t);
+            search_station!(maplewood_manchester, reader, t, realtime, line, count);
         }
         Station::Brentwood => {
-            search_station!(brentwood, reader, t);
+            search_station!(brentwood, reader, t, realtime, line, count);
         }
         Station::RichmondHeights => {
-            search_station!(richmond_heights, reader, t);
+            search_station!(richmond_heights, reader, t, realtime, line, count);
         }
         Station::Clayton => {
-            search_station!(clayton, reader, t);
+            search_station!(clayton, reader, t, realtime, line, count);
         }
         Station::Forsyth => {
-            search_station!(forsyth, reader, t);
+            search_station!(forsyth, reader, t, realtime, line, count);
         }
         Station::UCity => {
-            search_station!(u_city, reader, t);
+            search_station!(u_city, reader, t, realtime, line, count);
         }
         Station::Skinker => {
-            search_station!(skinker, reader, t);
+            search_station!(skinker, reader, t, realtime, line, count);
         }
         Station::ForestPark => {
-            search_station!(forest_park, reader, t);
+            search_station!(forest_park, reader, t, realtime, line, count);
         }
         Station::CWE => {
-            search_station!(cwe, reader, t);
+            search_station!(cwe, reader, t, realtime, line, count);
         }
         Station::Cortex => {
-            search_station!(cortex, reader, t);
+            search_station!(cortex, reader, t, realtime, line, count);
         }
         Station::Grand => {
-            search_station!(grand, reader, t);
+            search_station!(grand, reader, t, realtime, line, count);
         }
         Station::Union => {
-            search_station!(union, reader, t);
+            search_station!(union, reader, t, realtime, line, count);
         }
         Station::CivicCenter => {
-            search_station!(civic_center, reader, t);
+            search_station!(civic_center, reader, t, realtime, line, count);
         }
         Station::Stadium => {
-            search_station!(stadium, reader, t);
+            search_station!(stadium, reader, t, realtime, line, count);
         }
         Station::EighthPine => {
-            search_station!(eight_pine, reader, t);
+            search_station!(eight_pine, reader, t, realtime, line, count);
         }
         Station::ConventionCenter => {
-            search_station!(convention_center, reader, t);
+            search_station!(convention_center, reader, t, realtime, line, count);
         }
         Station::LacledesLanding => {
-            search_station!(lacledes_landing, reader, t);
+            search_station!(lacledes_landing, reader, t, realtime, line, count);
         }
         Station::EastRiverfront => {
-            search_station!(east_riverfront, reader, t);
+            search_station!(east_riverfront, reader, t, realtime, line, count);
         }
         Station::FifthMissouri => {
-            search_station!(fifth_missouri, reader, t);
+            search_station!(fifth_missouri, reader, t, realtime, line, count);
         }
         Station::EmersonPark => {
-            search_station!(emerson_park, reader, t);
+            search_station!(emerson_park, reader, t, realtime, line, count);
         }
         Station::JJK => {
-            search_station!(jjk, reader, t);
+            search_station!(jjk, reader, t, realtime, line, count);
         }
         Station::Washington => {
-            search_station!(washington, reader, t);
+            search_station!(washington, reader, t, realtime, line, count);
         }
         Station::FairviewHeights => {
-            search_station!(fairview_heights, reader, t);
+            search_station!(fairview_heights, reader, t, realtime, line, count);
         }
         Station::MemorialHospital => {
-            search_station!(memorial_hospital, reader, t);
+            search_station!(memorial_hospital, reader, t, realtime, line, count);
         }
         Station::Swansea => {
-            search_station!(swansea, reader, t);
+            search_station!(swansea, reader, t, realtime, line, count);
         }
         Station::Belleville => {
-            search_station!(belleville, reader, t);
+            search_station!(belleville, reader, t, realtime, line, count);
         }
         Station::College => {
-            search_station!(college, reader, t);
+            search_station!(college, reader, t, realtime, line, count);
         }
         Station::ShilohScott => {
-            search_station!(shiloh_scott, reader, t);
+            search_station!(shiloh_scott, reader, t, realtime, line, count);
         }
     }
 }
@@ -222,3 +397,87 @@ fn line_info(mut s: String) -> (String, String) {
     };
     (s, line.to_string())
 }
+
+/// Reads a timetable time like `5:08A`.
+fn clock_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(&format!("{time}M"), "%-I:%M%p").ok()
+}
+
+/// A timetable time moved by `delay` seconds.
+fn predicted_time(time: &str, delay: i32) -> Option<String> {
+    let predicted = clock_time(time)? + TimeDelta::seconds(delay.into());
+    let mut predicted = predicted.format("%-I:%M%p").to_string();
+    predicted.pop();
+    Some(predicted)
+}
+
+/// Minutes from `t` until a train timetabled at `time`, running `delay` seconds late, leaves.
+/// Times earlier in the day than `t` are after midnight.
+fn minutes_until(time: &str, delay: i32, t: DateTime<Local>) -> Option<i64> {
+    let leaves = clock_time(time)? + TimeDelta::seconds(delay.into());
+    let mut until = leaves.signed_duration_since(t.time());
+    if until < -TimeDelta::minutes(1) {
+        until += TimeDelta::days(1);
+    }
+    Some(until.num_minutes().max(0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    const TIMETABLE: &str = "Trip,Route,Forest ParkDeBaliviere Station,Grand Station\n\
+                             R1,RED,11:00PR,11:08PR\n\
+                             B1,BLUE,11:10PB,11:18PB\n\
+                             R2,RED,11:20PR,11:28PR\n\
+                             R3,RED,11:40PR,11:48PR\n";
+
+    #[test]
+    fn test_minutes_until() {
+        let t = Local.with_ymd_and_hms(2025, 5, 20, 23, 50, 0).unwrap();
+        assert_eq!(minutes_until("11:58P", 0, t), Some(8));
+        // a train just gone still shows as leaving now
+        assert_eq!(minutes_until("11:49P", 0, t), Some(0));
+        // past midnight is the next day, not a day ago
+        assert_eq!(minutes_until("12:05A", 0, t), Some(15));
+        // a delay can carry it over midnight too
+        assert_eq!(minutes_until("11:55P", 600, t), Some(15));
+        assert_eq!(minutes_until("11:40P", 900, t), Some(5));
+        assert_eq!(minutes_until("not a time", 0, t), None);
+    }
+
+    #[test]
+    fn test_find_departures() {
+        // every train in the table is still to come
+        let t = Local::now()
+            .with_hour(0)
+            .and_then(|t| t.with_minute(0))
+            .unwrap();
+        let trips = |departures: Vec<Scheduled>| -> Vec<Option<String>> {
+            departures
+                .into_iter()
+                .map(|departure| departure.trip)
+                .collect()
+        };
+        let station = Station::ForestPark;
+
+        let departures = find_departures(TIMETABLE.as_bytes(), &station, t, None, None, 2);
+        assert_eq!(trips(departures), [Some("R1".into()), Some("B1".into())]);
+
+        let departures =
+            find_departures(TIMETABLE.as_bytes(), &station, t, None, Some(Line::Red), 3);
+        assert_eq!(
+            trips(departures),
+            [Some("R1".into()), Some("R2".into()), Some("R3".into())]
+        );
+
+        let departures =
+            find_departures(TIMETABLE.as_bytes(), &station, t, None, Some(Line::Blue), 3);
+        assert_eq!(departures.len(), 1);
+        assert_eq!(
+            (departures[0].time.as_str(), departures[0].line.as_str()),
+            ("11:10P", "blue")
+        );
+    }
+}
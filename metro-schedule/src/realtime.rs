@@ -0,0 +1,475 @@
+use crate::read_location;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use prost::Message;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// `TripDescriptor.schedule_relationship` for a trip that won't run.
+const CANCELED: i32 = 3;
+
+/// How long one fetch may take before it's given up on.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A feed older than this says too little about trips running now to be used.
+const MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// What a GTFS-realtime feed says about trips running today. Only delays are used: a trip's
+/// delay is the one reported for the next stop it reaches, and is applied to every station
+/// after it. Feeds that give predicted times without delays aren't understood.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Realtime {
+    trips: HashMap<String, TripStatus>,
+    alerts: Vec<ServiceAlert>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TripStatus {
+    /// Seconds behind the timetable, or ahead when negative.
+    Delayed(i32),
+    Canceled,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ServiceAlert {
+    text: String,
+    /// Start and end of each period the alert is in effect, as Unix times. Empty means always.
+    periods: Vec<(Option<u64>, Option<u64>)>,
+    /// Everything the alert is about. Empty, or an entity naming neither a route nor a trip,
+    /// means the whole network.
+    routes: Vec<String>,
+    trips: Vec<String>,
+    network_wide: bool,
+}
+
+impl ServiceAlert {
+    fn is_active(&self, now: DateTime<Local>) -> bool {
+        let now = u64::try_from(now.timestamp()).unwrap_or_default();
+        self.periods.is_empty()
+            || self.periods.iter().any(|(start, end)| {
+                start.is_none_or(|start| start <= now) && end.is_none_or(|end| now <= end)
+            })
+    }
+
+    fn applies_to(&self, trip: Option<&str>, route: Option<&str>) -> bool {
+        self.network_wide
+            || trip.is_some_and(|trip| self.trips.iter().any(|t| t == trip))
+            || route.is_some_and(|route| self.routes.iter().any(|r| r == route))
+    }
+}
+
+impl Realtime {
+    /// Reads a TripUpdates and/or ServiceAlerts feed from a URL or a file path.
+    pub async fn fetch(location: &str) -> anyhow::Result<Self> {
+        let feed = tokio::time::timeout(FETCH_TIMEOUT, read_location(location))
+            .await
+            .context("timed out fetching the realtime feed")??;
+        Self::decode(&feed)
+    }
+
+    pub fn decode(feed: &[u8]) -> anyhow::Result<Self> {
+        let feed = FeedMessage::decode(feed)?;
+        let mut realtime = Self::default();
+        for entity in feed.entity {
+            if let Some(update) = entity.trip_update {
+                let Some(trip_id) = update.trip.trip_id else {
+                    continue;
+                };
+                let status = if update.trip.schedule_relationship == Some(CANCELED) {
+                    Some(TripStatus::Canceled)
+                } else {
+                    update
+                        .delay
+                        .or_else(|| {
+                            update.stop_time_update.iter().find_map(|stop| {
+                                stop.departure
+                                    .as_ref()
+                                    .and_then(|event| event.delay)
+                                    .or(stop.arrival.as_ref().and_then(|event| event.delay))
+                            })
+                        })
+                        .map(TripStatus::Delayed)
+                };
+                if let Some(status) = status {
+                    realtime.trips.insert(trip_id, status);
+                }
+            }
+            if let Some(alert) = entity.alert {
+                let Some(text) = alert.header_text.as_ref().and_then(TranslatedString::text) else {
+                    continue;
+                };
+                realtime.alerts.push(ServiceAlert {
+                    text,
+                    periods: alert
+                        .active_period
+                        .iter()
+                        .map(|period| (period.start, period.end))
+                        .collect(),
+                    routes: alert
+                        .informed_entity
+                        .iter()
+                        .filter_map(|entity| entity.route_id.clone())
+                        .collect(),
+                    trips: alert
+                        .informed_entity
+                        .iter()
+                        .filter_map(|entity| entity.trip.as_ref()?.trip_id.clone())
+                        .collect(),
+                    network_wide: alert.informed_entity.is_empty()
+                        || alert.informed_entity.iter().any(|entity| {
+                            entity.route_id.is_none()
+                                && entity.trip.is_none()
+                                && entity.stop_id.is_none()
+                        }),
+                });
+            }
+        }
+        Ok(realtime)
+    }
+
+    /// Seconds the trip is running behind, if the feed says anything about it.
+    pub fn delay(&self, trip: Option<&str>) -> Option<i32> {
+        match self.trips.get(trip?)? {
+            TripStatus::Delayed(delay) => Some(*delay),
+            TripStatus::Canceled => None,
+        }
+    }
+
+    pub fn is_canceled(&self, trip: Option<&str>) -> bool {
+        trip.and_then(|trip| self.trips.get(trip)) == Some(&TripStatus::Canceled)
+    }
+
+    /// Headlines of the alerts in effect at `now` for the trip or its route.
+    pub fn alerts(
+        &self,
+        trip: Option<&str>,
+        route: Option<&str>,
+        now: DateTime<Local>,
+    ) -> Vec<String> {
+        self.alerts
+            .iter()
+            .filter(|alert| alert.is_active(now) && alert.applies_to(trip, route))
+            .map(|alert| alert.text.clone())
+            .collect()
+    }
+}
+
+/// The last feed fetched, kept between polls so arrival requests never wait on the network.
+#[derive(Clone, Debug, Default)]
+pub struct LiveFeed(Arc<RwLock<Option<Fetched>>>);
+
+#[derive(Debug)]
+struct Fetched {
+    at: Instant,
+    realtime: Arc<Realtime>,
+}
+
+impl LiveFeed {
+    /// How often to fetch the feed again. Agencies typically update theirs every 30 seconds.
+    pub const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+    pub fn update(&self, realtime: Realtime) {
+        if let Ok(mut latest) = self.0.write() {
+            *latest = Some(Fetched {
+                at: Instant::now(),
+                realtime: Arc::new(realtime),
+            });
+        }
+    }
+
+    /// The last feed fetched, unless that was too long ago to still be accurate.
+    pub fn current(&self) -> Option<Arc<Realtime>> {
+        let latest = self.0.read().ok()?;
+        let fetched = latest.as_ref()?;
+        (fetched.at.elapsed() <= MAX_AGE).then(|| fetched.realtime.clone())
+    }
+}
+
+// The parts of gtfs-realtime.proto read above, with the same field tags. Anything else in a
+// feed is skipped when decoding.
+
+#[derive(Clone, PartialEq, Message)]
+struct FeedMessage {
+    #[prost(message, required, tag = "1")]
+    header: FeedHeader,
+    #[prost(message, repeated, tag = "2")]
+    entity: Vec<FeedEntity>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FeedHeader {
+    #[prost(string, required, tag = "1")]
+    gtfs_realtime_version: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FeedEntity {
+    #[prost(string, required, tag = "1")]
+    id: String,
+    #[prost(message, optional, tag = "3")]
+    trip_update: Option<TripUpdate>,
+    #[prost(message, optional, tag = "5")]
+    alert: Option<Alert>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TripUpdate {
+    #[prost(message, required, tag = "1")]
+    trip: TripDescriptor,
+    #[prost(message, repeated, tag = "2")]
+    stop_time_update: Vec<StopTimeUpdate>,
+    #[prost(int32, optional, tag = "5")]
+    delay: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TripDescriptor {
+    #[prost(string, optional, tag = "1")]
+    trip_id: Option<String>,
+    #[prost(int32, optional, tag = "4")]
+    schedule_relationship: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StopTimeUpdate {
+    #[prost(message, optional, tag = "2")]
+    arrival: Option<StopTimeEvent>,
+    #[prost(message, optional, tag = "3")]
+    departure: Option<StopTimeEvent>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StopTimeEvent {
+    #[prost(int32, optional, tag = "1")]
+    delay: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Alert {
+    #[prost(message, repeated, tag = "1")]
+    active_period: Vec<TimeRange>,
+    #[prost(message, repeated, tag = "5")]
+    informed_entity: Vec<EntitySelector>,
+    #[prost(message, optional, tag = "10")]
+    header_text: Option<TranslatedString>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TimeRange {
+    #[prost(uint64, optional, tag = "1")]
+    start: Option<u64>,
+    #[prost(uint64, optional, tag = "2")]
+    end: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EntitySelector {
+    #[prost(string, optional, tag = "2")]
+    route_id: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    trip: Option<TripDescriptor>,
+    #[prost(string, optional, tag = "5")]
+    stop_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TranslatedString {
+    #[prost(message, repeated, tag = "1")]
+    translation: Vec<Translation>,
+}
+
+impl TranslatedString {
+    /// The English text, or whichever comes first when there is none.
+    fn text(&self) -> Option<String> {
+        self.translation
+            .iter()
+            .find(|translation| {
+                translation
+                    .language
+                    .as_deref()
+                    .is_none_or(|language| language.starts_with("en"))
+            })
+            .or(self.translation.first())
+            .map(|translation| translation.text.clone())
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Translation {
+    #[prost(string, required, tag = "1")]
+    text: String,
+    #[prost(string, optional, tag = "2")]
+    language: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_fixture_feed() {
+        let realtime = Realtime::fetch("fixtures/realtime.pb").await.unwrap();
+        // no trip-wide delay, so the next stop's departure delay counts
+        assert_eq!(realtime.delay(Some("R1")), Some(300));
+        assert_eq!(realtime.delay(Some("B1")), Some(-60));
+        assert_eq!(realtime.delay(Some("R2")), None);
+        assert_eq!(realtime.delay(None), None);
+        assert!(realtime.is_canceled(Some("R3")));
+        assert!(!realtime.is_canceled(Some("R1")));
+
+        let during = Local.timestamp_opt(1747735200, 0).unwrap();
+        assert_eq!(
+            realtime.alerts(Some("R1"), Some("RED"), during),
+            vec!["Red Line trains delayed near Grand"]
+        );
+        assert!(realtime.alerts(Some("B1"), Some("BLUE"), during).is_empty());
+        let after = Local.timestamp_opt(1747760401, 0).unwrap();
+        assert!(realtime.alerts(Some("R1"), Some("RED"), after).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_live_feed() {
+        let live = LiveFeed::default();
+        assert!(live.current().is_none());
+        live.update(Realtime::fetch("fixtures/realtime.pb").await.unwrap());
+        assert_eq!(live.current().unwrap().delay(Some("R1")), Some(300));
+    }
+
+    fn trip(trip_id: &str) -> TripDescriptor {
+        TripDescriptor {
+            trip_id: Some(trip_id.to_string()),
+            schedule_relationship: None,
+        }
+    }
+
+    fn update(id: &str, update: TripUpdate) -> FeedEntity {
+        FeedEntity {
+            id: id.to_string(),
+            trip_update: Some(update),
+            alert: None,
+        }
+    }
+
+    fn alert(id: &str, text: &str, informed_entity: Vec<EntitySelector>) -> FeedEntity {
+        FeedEntity {
+            id: id.to_string(),
+            trip_update: None,
+            alert: Some(Alert {
+                active_period: vec![TimeRange {
+                    start: Some(1000),
+                    end: Some(2000),
+                }],
+                informed_entity,
+                header_text: Some(TranslatedString {
+                    translation: vec![
+                        Translation {
+                            text: format!("{text} (es)"),
+                            language: Some("es".to_string()),
+                        },
+                        Translation {
+                            text: text.to_string(),
+                            language: Some("en-US".to_string()),
+                        },
+                    ],
+                }),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let stop = |arrival: Option<i32>, departure: Option<i32>| StopTimeUpdate {
+            arrival: arrival.map(|delay| StopTimeEvent { delay: Some(delay) }),
+            departure: departure.map(|delay| StopTimeEvent { delay: Some(delay) }),
+        };
+        let feed = FeedMessage {
+            header: FeedHeader {
+                gtfs_realtime_version: "2.0".to_string(),
+            },
+            entity: vec![
+                // the trip-wide delay wins over the stops'
+                update(
+                    "1",
+                    TripUpdate {
+                        trip: trip("R1"),
+                        stop_time_update: vec![stop(None, Some(60))],
+                        delay: Some(120),
+                    },
+                ),
+                // an arrival delay does when there's no departure one
+                update(
+                    "2",
+                    TripUpdate {
+                        trip: trip("R2"),
+                        stop_time_update: vec![stop(Some(-30), None), stop(None, Some(90))],
+                        delay: None,
+                    },
+                ),
+                update(
+                    "3",
+                    TripUpdate {
+                        trip: TripDescriptor {
+                            schedule_relationship: Some(CANCELED),
+                            ..trip("R3")
+                        },
+                        stop_time_update: vec![stop(None, Some(60))],
+                        delay: None,
+                    },
+                ),
+                alert(
+                    "4",
+                    "Blue Line single tracking",
+                    vec![EntitySelector {
+                        route_id: Some("BLUE".to_string()),
+                        ..Default::default()
+                    }],
+                ),
+                alert(
+                    "5",
+                    "Train R2 held at Grand",
+                    vec![EntitySelector {
+                        trip: Some(trip("R2")),
+                        ..Default::default()
+                    }],
+                ),
+                alert("6", "Expect crowding downtown", Vec::new()),
+            ],
+        };
+        let realtime = Realtime::decode(&feed.encode_to_vec()).unwrap();
+
+        assert_eq!(realtime.delay(Some("R1")), Some(120));
+        assert_eq!(realtime.delay(Some("R2")), Some(-30));
+        assert_eq!(realtime.delay(Some("R3")), None);
+        assert!(realtime.is_canceled(Some("R3")));
+        assert!(!realtime.is_canceled(Some("R2")));
+
+        let during = Local.timestamp_opt(1500, 0).unwrap();
+        assert_eq!(
+            realtime.alerts(Some("B1"), Some("BLUE"), during),
+            vec!["Blue Line single tracking", "Expect crowding downtown"]
+        );
+        assert_eq!(
+            realtime.alerts(Some("R2"), Some("RED"), during),
+            vec!["Train R2 held at Grand", "Expect crowding downtown"]
+        );
+        let before = Local.timestamp_opt(999, 0).unwrap();
+        assert!(realtime.alerts(Some("R2"), Some("BLUE"), before).is_empty());
+    }
+
+    #[test]
+    fn test_stale_feed() {
+        let live = LiveFeed::default();
+        live.update(Realtime::default());
+        assert!(live.current().is_some());
+
+        let long_ago = Instant::now().checked_sub(MAX_AGE + Duration::from_secs(1));
+        let Some(at) = long_ago else {
+            // the clock hasn't run long enough to have a feed that old
+            return;
+        };
+        live.0.write().unwrap().as_mut().unwrap().at = at;
+        assert!(live.current().is_none());
+    }
+}
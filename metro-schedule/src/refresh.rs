@@ -0,0 +1,121 @@
+use crate::{Schedules, Source, StationTimeSlice};
+use anyhow::{bail, Context};
+use csv::Reader;
+use std::path::Path;
+use std::sync::RwLock;
+
+impl Schedules {
+    /// Checks every table reads as rows of station times, so a changed or broken page doesn't
+    /// replace a working timetable.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, table) in self.tables() {
+            let mut times = 0;
+            for row in Reader::from_reader(table.as_bytes()).deserialize::<StationTimeSlice>() {
+                times += row
+                    .with_context(|| format!("{name} has a malformed row"))?
+                    .times()
+                    .count();
+            }
+            if times == 0 {
+                bail!("{name} has no arrival times");
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether both have the same timetables, whenever they were fetched.
+    pub fn same_timetable(&self, other: &Self) -> bool {
+        self.tables()
+            .iter()
+            .zip(other.tables())
+            .all(|((_, a), (_, b))| *a == b)
+    }
+}
+
+/// Swaps `fresh` in for the current schedules if it validates. Returns whether the timetable
+/// itself changed; an identical one still replaces the old so the fetch time moves on.
+pub fn apply(current: &RwLock<Schedules>, fresh: Schedules) -> anyhow::Result<bool> {
+    fresh.validate()?;
+    let mut current = current
+        .write()
+        .map_err(|_| anyhow::anyhow!("schedules lock poisoned"))?;
+    let changed = !current.same_timetable(&fresh);
+    *current = fresh;
+    Ok(changed)
+}
+
+/// Fetches the schedules from `source` again and swaps them in if they validate, keeping a
+/// copy in `cache`. Posts to `notifications` when the timetable changes. An error means the
+/// current schedules were kept, or weren't cached, and says which.
+pub async fn refresh(
+    schedules: &RwLock<Schedules>,
+    source: &Source,
+    cache: Option<&Path>,
+    notifications: Option<&str>,
+) -> anyhow::Result<()> {
+    let changed = match source.fetch().await {
+        Ok(fresh) => apply(schedules, fresh),
+        Err(e) => Err(e),
+    }
+    .context("keeping the current schedules")?;
+    if let (true, Some(url)) = (changed, notifications) {
+        let _ = reqwest::Client::new()
+            .post(url)
+            .body("The Metro timetable changed. Arrival times now follow the new schedule.")
+            .send()
+            .await;
+    }
+    if let (Some(dir), Ok(current)) = (cache, schedules.read()) {
+        current
+            .save(dir)
+            .with_context(|| format!("couldn't cache schedules in {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Local;
+
+    fn schedules(table: &str) -> Schedules {
+        Schedules {
+            weekday_west: table.to_string(),
+            weekday_east: table.to_string(),
+            weekend_west: table.to_string(),
+            weekend_east: table.to_string(),
+            fetched_at: Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let table = "ShilohScott Station,College Station\n5:01AR,5:05AR\n";
+        let current = RwLock::new(schedules(table));
+
+        assert!(!apply(&current, schedules(table)).unwrap());
+        assert!(apply(&current, schedules("College Station\n5:06AR\n")).unwrap());
+        assert_eq!(
+            current.read().unwrap().weekday_west,
+            "College Station\n5:06AR\n"
+        );
+
+        // an error page has no station columns, so nothing gets swapped in
+        assert!(apply(&current, schedules("Service Unavailable\n")).is_err());
+        assert!(apply(&current, schedules("")).is_err());
+        assert_eq!(
+            current.read().unwrap().weekday_west,
+            "College Station\n5:06AR\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_schedules_when_fetching_fails() {
+        let table = "College Station\n5:06AR\n";
+        let current = RwLock::new(schedules(table));
+        let source = Source::Gtfs("/nonexistent/gtfs.zip".to_string());
+        let e = refresh(&current, &source, None, None).await.unwrap_err();
+        assert!(e.to_string().starts_with("keeping the current schedules"));
+        assert_eq!(current.read().unwrap().weekday_west, table);
+    }
+}
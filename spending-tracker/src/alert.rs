@@ -0,0 +1,87 @@
+use crate::Category;
+use serde_derive::{Deserialize, Serialize};
+
+/// Where budget alerts go and when they fire.
+#[derive(Clone, Debug)]
+pub struct AlertConfig {
+    /// Percentages of a budget that trigger an alert, e.g. `[50, 80, 100]`.
+    pub thresholds: Vec<u32>,
+    /// notification-service `/notifications` endpoint. Alerts are off without one.
+    pub url: Option<String>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            thresholds: vec![50, 80, 100],
+            url: None,
+        }
+    }
+}
+
+impl AlertConfig {
+    /// Reads thresholds such as `50,80,100`, ignoring anything that isn't a number.
+    pub fn parse_thresholds(s: &str) -> Vec<u32> {
+        let mut thresholds: Vec<u32> = s
+            .split(',')
+            .filter_map(|t| t.trim().trim_end_matches('%').parse().ok())
+            .filter(|t| *t > 0)
+            .collect();
+        thresholds.sort();
+        thresholds.dedup();
+        thresholds
+    }
+}
+
+/// A threshold that has already been alerted on this period. `category` is `None` for the
+/// overall budget.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Alert {
+    pub category: Option<Category>,
+    pub threshold: u32,
+}
+
+/// An alert message on its way to notification-service, and the thresholds it reports.
+/// They only count as sent once it has been delivered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outgoing {
+    pub message: String,
+    pub alerts: Vec<Alert>,
+}
+
+/// Thresholds reached by spending `spent` of `budget` that are not in `sent`. Only the
+/// highest should be announced, but all of them count as sent.
+pub fn crossed(thresholds: &[u32], spent: i64, budget: i64, sent: &[u32]) -> Vec<u32> {
+    if budget <= 0 {
+        return Vec::new();
+    }
+    thresholds
+        .iter()
+        .copied()
+        .filter(|threshold| spent * 100 >= i64::from(*threshold) * budget)
+        .filter(|threshold| !sent.contains(threshold))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crossed() {
+        let thresholds = [50, 80, 100];
+        assert!(crossed(&thresholds, 4999, 10000, &[]).is_empty());
+        assert_eq!(crossed(&thresholds, 5000, 10000, &[]), vec![50]);
+        assert_eq!(crossed(&thresholds, 12000, 10000, &[50]), vec![80, 100]);
+        assert!(crossed(&thresholds, 12000, 10000, &[50, 80, 100]).is_empty());
+        assert!(crossed(&thresholds, 100, 0, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_thresholds() {
+        assert_eq!(
+            AlertConfig::parse_thresholds("80, 50%,x,100,0"),
+            vec![50, 80, 100]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use crate::Category;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// A category along with the other words that mean it, e.g. `food` for Dining.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CategoryInfo {
+    pub name: Category,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Archived categories keep their history but can't be charged to anymore.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl CategoryInfo {
+    fn new(name: Category, aliases: &[&str]) -> Self {
+        Self {
+            name,
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            archived: false,
+        }
+    }
+
+    /// Whether `word` is this category's name or one of its aliases, ignoring case.
+    pub fn is_called(&self, word: &str) -> bool {
+        let word = word.trim();
+        self.name.name().eq_ignore_ascii_case(word)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(word))
+    }
+}
+
+/// Changes to a category. Fields left out are kept as they are.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CategoryPatch {
+    /// A new name for the category. Everything filed under the old name moves with it.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+    #[serde(default)]
+    pub archived: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MergeRequest {
+    /// The category to fold the merged one into.
+    pub into: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CategoryError {
+    NotFound(String),
+    Exists(String),
+    /// Other is where uncategorized spending goes, so it has to stay put.
+    Reserved,
+    Unnamed,
+}
+
+impl fmt::Display for CategoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound(name) => write!(f, "no category called {name}"),
+            Self::Exists(name) => write!(f, "{name} is already a category or alias"),
+            Self::Reserved => write!(f, "{} can't be changed", Category::Other),
+            Self::Unnamed => write!(f, "categories need a name"),
+        }
+    }
+}
+
+impl std::error::Error for CategoryError {}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(transparent)]
+pub struct Categories(pub Vec<CategoryInfo>);
+
+impl Default for Categories {
+    fn default() -> Self {
+        Self(vec![
+            CategoryInfo::new(Category::Dining, &["food", "restaurant"]),
+            CategoryInfo::new(Category::Grocery, &["groceries"]),
+            CategoryInfo::new(Category::Travel, &["trip"]),
+            CategoryInfo::new(Category::Merchandise, &["shopping"]),
+            CategoryInfo::new(Category::Entertainment, &["fun"]),
+            CategoryInfo::new(Category::Other, &[]),
+        ])
+    }
+}
+
+impl Categories {
+    /// The category called `word`, archived or not.
+    pub fn find(&self, word: &str) -> Option<&CategoryInfo> {
+        self.0.iter().find(|info| info.is_called(word))
+    }
+
+    /// The category new spending called `word` should be filed under.
+    pub fn resolve(&self, word: &str) -> Option<Category> {
+        self.find(word)
+            .filter(|info| !info.archived)
+            .map(|info| info.name.clone())
+    }
+
+    /// Adds `category` if it isn't known yet, e.g. when it arrives in an import.
+    pub fn ensure(&mut self, category: &Category) {
+        if self.find(category.name()).is_none() {
+            self.0.push(CategoryInfo::new(category.clone(), &[]));
+        }
+    }
+
+    /// Adds the categories in `other` that aren't known here yet, aliases and all.
+    pub fn absorb(&mut self, other: &Categories) {
+        for info in &other.0 {
+            if self.find(info.name.name()).is_none() {
+                self.0.push(info.clone());
+            }
+        }
+    }
+
+    pub fn create(&mut self, info: CategoryInfo) -> Result<(), CategoryError> {
+        let name = Category::new(info.name.name());
+        if name.name().is_empty() {
+            return Err(CategoryError::Unnamed);
+        }
+        self.check_free(name.name(), None)?;
+        for alias in &info.aliases {
+            self.check_free(alias, None)?;
+        }
+        self.0.push(CategoryInfo {
+            name,
+            aliases: clean(info.aliases),
+            archived: info.archived,
+        });
+        Ok(())
+    }
+
+    /// Applies `patch` to the category called `name`. Returns the old and new name, which
+    /// differ if it was renamed.
+    pub fn update(
+        &mut self,
+        name: &str,
+        patch: CategoryPatch,
+    ) -> Result<(Category, Category), CategoryError> {
+        let index = self.index_of(name)?;
+        let old = self.0[index].name.clone();
+        let renamed = patch.name.as_deref().map(Category::new);
+        if old == Category::Other && (renamed.is_some() || patch.archived == Some(true)) {
+            return Err(CategoryError::Reserved);
+        }
+        if let Some(new) = &renamed {
+            if new.name().is_empty() {
+                return Err(CategoryError::Unnamed);
+            }
+            self.check_free(new.name(), Some(index))?;
+        }
+        for alias in patch.aliases.iter().flatten() {
+            self.check_free(alias, Some(index))?;
+        }
+
+        let info = &mut self.0[index];
+        if let Some(new) = renamed {
+            info.name = new;
+        }
+        if let Some(aliases) = patch.aliases {
+            info.aliases = clean(aliases);
+        }
+        if let Some(archived) = patch.archived {
+            info.archived = archived;
+        }
+        Ok((old, info.name.clone()))
+    }
+
+    /// Removes the category called `from`, making its name and aliases aliases of `into`.
+    /// Returns both names so callers can move what was filed under the old one.
+    pub fn merge(&mut self, from: &str, into: &str) -> Result<(Category, Category), CategoryError> {
+        let from = self.index_of(from)?;
+        let into = self.index_of(into)?;
+        if self.0[from].name == Category::Other {
+            return Err(CategoryError::Reserved);
+        }
+        if from == into {
+            return Err(CategoryError::Exists(self.0[from].name.to_string()));
+        }
+        let merged = self.0.remove(from);
+        let target = &mut self.0[if into > from { into - 1 } else { into }];
+        target.aliases.push(merged.name.to_string());
+        target.aliases.extend(merged.aliases);
+        Ok((merged.name, target.name.clone()))
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize, CategoryError> {
+        self.0
+            .iter()
+            .position(|info| info.is_called(name))
+            .ok_or_else(|| CategoryError::NotFound(name.to_string()))
+    }
+
+    /// Errors if `word` already names a category other than the one at `except`.
+    fn check_free(&self, word: &str, except: Option<usize>) -> Result<(), CategoryError> {
+        match self.0.iter().position(|info| info.is_called(word)) {
+            Some(index) if Some(index) != except => Err(CategoryError::Exists(word.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn clean(aliases: Vec<String>) -> Vec<String> {
+    aliases
+        .into_iter()
+        .map(|alias| alias.trim().to_lowercase())
+        .filter(|alias| !alias.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pets() -> CategoryInfo {
+        CategoryInfo {
+            name: Category::new("Pets"),
+            aliases: vec!["Dog".to_string(), "vet".to_string()],
+            archived: false,
+        }
+    }
+
+    #[test]
+    fn test_create_and_resolve() {
+        let mut categories = Categories::default();
+        assert_eq!(categories.resolve("FOOD"), Some(Category::Dining));
+        assert_eq!(categories.resolve("pets"), None);
+
+        categories.create(pets()).unwrap();
+        assert_eq!(categories.resolve("dog"), Some(Category::new("Pets")));
+        assert_eq!(
+            categories.create(pets()),
+            Err(CategoryError::Exists("Pets".to_string()))
+        );
+        let blank = CategoryInfo {
+            name: Category::new("  "),
+            ..pets()
+        };
+        assert_eq!(categories.create(blank), Err(CategoryError::Unnamed));
+        let rename = CategoryPatch {
+            name: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            categories.update("pets", rename),
+            Err(CategoryError::Unnamed)
+        );
+    }
+
+    #[test]
+    fn test_absorb() {
+        let mut categories = Categories::default();
+        let mut other = Categories::default();
+        other.create(pets()).unwrap();
+        categories.absorb(&other);
+        categories.absorb(&other);
+        assert_eq!(categories, other);
+    }
+
+    #[test]
+    fn test_rename_and_archive() {
+        let mut categories = Categories::default();
+        categories.create(pets()).unwrap();
+        let (old, new) = categories
+            .update(
+                "vet",
+                CategoryPatch {
+                    name: Some("Animals".to_string()),
+                    archived: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!((old.name(), new.name()), ("Pets", "Animals"));
+        assert_eq!(categories.resolve("dog"), None);
+        assert!(categories.find("dog").is_some_and(|info| info.archived));
+
+        assert_eq!(
+            categories.update(
+                "other",
+                CategoryPatch {
+                    name: Some("Misc".to_string()),
+                    ..Default::default()
+                }
+            ),
+            Err(CategoryError::Reserved)
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut categories = Categories::default();
+        let (from, into) = categories.merge("fun", "trip").unwrap();
+        assert_eq!((from, into), (Category::Entertainment, Category::Travel));
+        assert_eq!(categories.resolve("entertainment"), Some(Category::Travel));
+        assert_eq!(categories.resolve("fun"), Some(Category::Travel));
+        assert!(categories.merge("other", "dining").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::ledger::to_minor;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The currency the ledger, budgets and reports are kept in.
+pub const BASE: &iso::Currency = iso::USD;
+
+/// How many units of the base currency one unit of each currency buys, keyed by ISO code.
+pub type Rates = BTreeMap<String, Decimal>;
+
+/// The ISO 4217 currency with the given code, in any case.
+pub fn find(code: &str) -> Option<&'static iso::Currency> {
+    iso::find(&code.trim().to_ascii_uppercase())
+}
+
+/// What a charge in a foreign currency originally came to. `amount` is in that currency's
+/// minor units.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Foreign {
+    pub amount: i64,
+    pub currency: String,
+}
+
+impl Foreign {
+    pub fn money(&self) -> Option<Money<'static, iso::Currency>> {
+        find(&self.currency).map(|currency| Money::from_minor(self.amount, currency))
+    }
+}
+
+impl fmt::Display for Foreign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.money() {
+            Some(money) => write!(f, "{money}"),
+            None => write!(f, "{} {}", self.amount, self.currency),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConversionError {
+    UnknownCurrency(String),
+    NoRate(String),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownCurrency(code) => write!(f, "unknown currency {code}"),
+            Self::NoRate(code) => write!(f, "no exchange rate for {code}"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Converts a bot-supplied amount in the currency with the given code into base currency
+/// minor units, along with the original amount if it was foreign.
+pub fn convert(
+    amount: f32,
+    code: &str,
+    rates: &Rates,
+) -> Result<(i64, Option<Foreign>), ConversionError> {
+    let currency = find(code).ok_or_else(|| ConversionError::UnknownCurrency(code.to_string()))?;
+    let amount = Decimal::from_f32(amount).unwrap_or_default();
+    let original = to_minor(&Money::from_decimal(amount, currency));
+    if currency == BASE {
+        return Ok((original, None));
+    }
+    let rate = rates
+        .get(currency.iso_alpha_code)
+        .ok_or_else(|| ConversionError::NoRate(currency.iso_alpha_code.to_string()))?;
+    Ok((
+        to_minor(&Money::from_decimal(amount * rate, BASE)),
+        Some(Foreign {
+            amount: original,
+            currency: currency.iso_alpha_code.to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let rates = Rates::from([
+            ("EUR".to_string(), Decimal::new(108, 2)),
+            ("JPY".to_string(), Decimal::new(67, 4)),
+        ]);
+
+        let (minor, foreign) = convert(12.5, "eur", &rates).unwrap();
+        assert_eq!(minor, 1350);
+        let foreign = foreign.unwrap();
+        assert_eq!((foreign.amount, foreign.currency.as_str()), (1250, "EUR"));
+
+        let (minor, foreign) = convert(1500.0, "JPY", &rates).unwrap();
+        assert_eq!(minor, 1005);
+        assert_eq!(foreign.unwrap().amount, 1500);
+
+        assert_eq!(convert(24.78, "USD", &rates).unwrap(), (2478, None));
+        assert_eq!(
+            convert(10.0, "CAD", &rates),
+            Err(ConversionError::NoRate("CAD".to_string()))
+        );
+        assert_eq!(
+            convert(10.0, "XYZ", &rates),
+            Err(ConversionError::UnknownCurrency("XYZ".to_string()))
+        );
+    }
+}
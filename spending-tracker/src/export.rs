@@ -0,0 +1,268 @@
+use crate::currency::{self, Foreign};
+use crate::ledger::{to_minor, usd, Entry, Split};
+use crate::{normalize_tag, Category, Kind};
+use chrono::{DateTime, Local, NaiveDate};
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// One row of an exported ledger. The same shape is used for CSV and JSON so either can be
+/// imported back.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Record {
+    pub id: u64,
+    pub time: DateTime<Local>,
+    pub amount: Decimal,
+    pub category: Category,
+    #[serde(default)]
+    pub kind: Kind,
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Amount in the currency it was charged in, if that wasn't the base currency.
+    #[serde(default)]
+    pub original_amount: Option<Decimal>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Space separated, so the CSV keeps one column per field.
+    #[serde(default)]
+    pub tags: String,
+    /// How a split charge divides, as `category=amount` pairs separated by `;`.
+    #[serde(default)]
+    pub splits: String,
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub user_name: Option<String>,
+}
+
+impl From<&Entry> for Record {
+    fn from(entry: &Entry) -> Self {
+        Self {
+            id: entry.id,
+            time: entry.time,
+            amount: *entry.money().amount(),
+            category: entry.category.clone(),
+            kind: entry.kind,
+            source: entry.source.clone(),
+            original_amount: entry
+                .foreign
+                .as_ref()
+                .and_then(Foreign::money)
+                .map(|money| *money.amount()),
+            currency: entry.foreign.as_ref().map(|f| f.currency.clone()),
+            merchant: entry.merchant.clone(),
+            note: entry.note.clone(),
+            tags: entry.tags.join(" "),
+            splits: entry
+                .splits
+                .iter()
+                .map(|split| format!("{}={}", split.category, usd(split.amount).amount()))
+                .collect::<Vec<_>>()
+                .join(";"),
+            user_id: entry.user_id,
+            user_name: entry.user_name.clone(),
+        }
+    }
+}
+
+impl Record {
+    pub fn minor(&self) -> i64 {
+        to_minor(&Money::from_decimal(self.amount, iso::USD))
+    }
+
+    fn foreign(&self) -> Option<Foreign> {
+        let currency = currency::find(self.currency.as_deref()?)?;
+        Some(Foreign {
+            amount: to_minor(&Money::from_decimal(self.original_amount?, currency)),
+            currency: currency.iso_alpha_code.to_string(),
+        })
+    }
+
+    fn splits(&self) -> Vec<Split> {
+        self.splits
+            .split(';')
+            .filter_map(|split| {
+                let (category, amount) = split.rsplit_once('=')?;
+                Some(Split {
+                    category: Category::new(category),
+                    amount: to_minor(&Money::from_decimal(amount.trim().parse().ok()?, iso::USD)),
+                })
+            })
+            .collect()
+    }
+
+    /// The ledger entry this record describes. Its id is reassigned when added to a ledger.
+    pub fn entry(&self) -> Entry {
+        let mut entry = Entry {
+            id: self.id,
+            amount: self.minor(),
+            category: self.category.clone(),
+            time: self.time,
+            kind: self.kind,
+            source: self.source.clone(),
+            foreign: self.foreign(),
+            merchant: self.merchant.clone(),
+            note: self.note.clone(),
+            tags: self.tags.split_whitespace().map(normalize_tag).collect(),
+            user_id: self.user_id,
+            user_name: self.user_name.clone(),
+            ..Default::default()
+        };
+        let splits = self.splits();
+        if !splits.is_empty() {
+            entry.set_splits(splits);
+        }
+        entry
+    }
+}
+
+/// Which entries an export covers. Without dates it has everything the ledger kept, closed
+/// periods as well as the current one.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExportQuery {
+    /// First day to include.
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    /// Last day to include.
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+}
+
+impl ExportQuery {
+    pub fn includes(&self, entry: &Entry) -> bool {
+        let date = entry.time.date_naive();
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub duplicates: usize,
+    /// Statement lines that were not spending, such as deposits and card payments.
+    #[serde(default)]
+    pub skipped: usize,
+}
+
+impl fmt::Display for ImportResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "imported: {}\nduplicates: {}",
+            self.imported, self.duplicates
+        )?;
+        if self.skipped > 0 {
+            write!(f, "\nskipped: {}", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn to_csv<'e>(entries: impl IntoIterator<Item = &'e Entry>) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for entry in entries {
+        writer.serialize(Record::from(entry))?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+pub fn from_csv(data: &[u8]) -> Result<Vec<Record>, csv::Error> {
+    csv::Reader::from_reader(data).deserialize().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ledger::usd;
+
+    #[test]
+    fn test_csv_round_trip() {
+        let entries = vec![
+            Entry {
+                id: 1,
+                amount: 2478,
+                category: Category::Dining,
+                time: "2025-01-01T12:00:00-06:00".parse().unwrap(),
+                merchant: Some("Pappy's, Smokehouse".to_string()),
+                note: Some("ribs".to_string()),
+                tags: vec!["date".to_string(), "bbq".to_string()],
+                user_id: Some(7),
+                user_name: Some("Sam".to_string()),
+                ..Default::default()
+            },
+            Entry {
+                id: 2,
+                amount: 150000,
+                category: Category::Travel,
+                time: "2025-01-02T08:30:00-06:00".parse().unwrap(),
+                ..Default::default()
+            },
+            Entry {
+                id: 3,
+                amount: 120000,
+                category: Category::Other,
+                time: "2025-01-03T09:00:00-06:00".parse().unwrap(),
+                kind: Kind::Income,
+                source: Some("salary".to_string()),
+                ..Default::default()
+            },
+            Entry {
+                id: 4,
+                amount: 8000,
+                category: Category::Grocery,
+                time: "2025-01-05T10:00:00-06:00".parse().unwrap(),
+                splits: vec![
+                    Split {
+                        category: Category::Grocery,
+                        amount: 5500,
+                    },
+                    Split {
+                        category: Category::new("Home & Garden"),
+                        amount: 2500,
+                    },
+                ],
+                ..Default::default()
+            },
+            Entry {
+                id: 5,
+                amount: 1350,
+                category: Category::Dining,
+                time: "2025-01-04T20:00:00-06:00".parse().unwrap(),
+                foreign: Some(Foreign {
+                    amount: 1250,
+                    currency: "EUR".to_string(),
+                }),
+                ..Default::default()
+            },
+        ];
+        let csv = to_csv(&entries).unwrap();
+        assert!(csv.starts_with(
+            "id,time,amount,category,kind,source,original_amount,currency,merchant,note,tags,splits,user_id,user_name\n1,"
+        ));
+
+        let records = from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[4].entry().foreign, entries[4].foreign);
+        assert_eq!(records[3].splits, "Grocery=55.00;Home & Garden=25.00");
+        assert_eq!(records[3].entry().splits, entries[3].splits);
+        assert_eq!(records[0].entry().merchant, entries[0].merchant);
+        assert_eq!(records[0].entry().tags, entries[0].tags);
+        assert_eq!(records[0].entry().user(), Some("Sam".to_string()));
+        assert_eq!(records[2].entry().source.as_deref(), Some("salary"));
+        assert!(records
+            .iter()
+            .zip(&entries)
+            .all(|(r, e)| r.entry().is_duplicate_of(e)));
+        assert_eq!(usd(records[1].minor()).to_string(), "$1,500.00");
+
+        // exports from before transactions had a kind are all expenses
+        let old = from_csv(b"id,time,amount,category\n1,2025-01-01T12:00:00-06:00,24.78,Dining\n");
+        assert_eq!(old.unwrap()[0].kind, Kind::Expense);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::ledger::{usd, Entry};
+use chrono::{Datelike, NaiveDate};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where spending is heading by the end of the budget period.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Forecast {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub spent: String,
+    pub budget: String,
+    /// End of period spending if the average so far keeps up.
+    pub linear: String,
+    /// End of period spending if each remaining day goes like the same weekday usually does.
+    pub weighted: String,
+    /// The day spending reached, or is expected to reach, the budget. `None` if the budget
+    /// should last the period.
+    pub exhausted_on: Option<NaiveDate>,
+}
+
+impl fmt::Display for Forecast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "forecast: {} by {} ({} by weekday)",
+            self.linear,
+            self.end.format("%b %-d"),
+            self.weighted
+        )?;
+        match self.exhausted_on {
+            Some(date) => write!(f, "\nbudget runs out {}", date.format("%b %-d")),
+            None => write!(f, "\nbudget lasts the period"),
+        }
+    }
+}
+
+/// Projects spending for the period from `start` to `end` as of `today`. `entries` can reach
+/// back before `start`; older ones only shape the weekday pattern. `available` is the
+/// period's budget in minor units.
+pub fn build<'e>(
+    entries: impl IntoIterator<Item = &'e Entry>,
+    today: NaiveDate,
+    start: NaiveDate,
+    end: NaiveDate,
+    available: i64,
+) -> Forecast {
+    let today = today.clamp(start, end);
+    let mut daily: Vec<(NaiveDate, i64)> = Vec::new();
+    for entry in entries {
+        let date = entry.time.date_naive();
+        if date <= today {
+            daily.push((date, entry.spending()));
+        }
+    }
+    daily.sort_by_key(|(date, _)| *date);
+
+    let spent: i64 = daily
+        .iter()
+        .filter(|(date, _)| *date >= start)
+        .map(|(_, amount)| amount)
+        .sum();
+    let elapsed = (today - start).num_days() + 1;
+    let length = (end - start).num_days() + 1;
+    let linear_rate = spent / elapsed;
+    let linear = spent * length / elapsed;
+
+    let weekdays = weekday_averages(&daily, today);
+    let remaining = days(today.succ_opt().unwrap_or(today), end);
+    let projected_day = |date: NaiveDate| {
+        weekdays
+            .map(|averages| averages[date.weekday().num_days_from_monday() as usize])
+            .unwrap_or(linear_rate)
+    };
+    let weighted = spent
+        + remaining
+            .iter()
+            .map(|date| projected_day(*date))
+            .sum::<i64>();
+
+    let mut exhausted_on = None;
+    if available > 0 {
+        let mut running = 0;
+        for date in days(start, end) {
+            running += if date <= today {
+                daily
+                    .iter()
+                    .filter(|(day, _)| *day == date)
+                    .map(|(_, amount)| amount)
+                    .sum()
+            } else {
+                projected_day(date)
+            };
+            if running >= available {
+                exhausted_on = Some(date);
+                break;
+            }
+        }
+    }
+
+    Forecast {
+        start,
+        end,
+        spent: usd(spent).to_string(),
+        budget: usd(available).to_string(),
+        linear: usd(linear).to_string(),
+        weighted: usd(weighted).to_string(),
+        exhausted_on,
+    }
+}
+
+/// Fewer days than this say more about chance than about which weekdays are expensive.
+const MIN_HISTORY_DAYS: i64 = 14;
+
+/// Average spending on each weekday, Monday first, over every full day before `today` back to
+/// the first entry. `None` without two weeks to learn from.
+fn weekday_averages(daily: &[(NaiveDate, i64)], today: NaiveDate) -> Option<[i64; 7]> {
+    let first = daily.first()?.0;
+    let yesterday = today.pred_opt()?;
+    if (yesterday - first).num_days() + 1 < MIN_HISTORY_DAYS {
+        return None;
+    }
+    let mut totals = [0; 7];
+    let mut counts = [0; 7];
+    for date in days(first, yesterday) {
+        counts[date.weekday().num_days_from_monday() as usize] += 1;
+    }
+    for (date, amount) in daily.iter().filter(|(date, _)| *date <= yesterday) {
+        totals[date.weekday().num_days_from_monday() as usize] += amount;
+    }
+    Some(std::array::from_fn(|day| totals[day] / counts[day].max(1)))
+}
+
+fn days(from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+    from.iter_days().take_while(|date| *date <= to).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn spent(y: i32, m: u32, d: u32, amount: i64) -> Entry {
+        Entry {
+            amount,
+            time: Local.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_linear_forecast() {
+        let start = NaiveDate::from_ymd_opt(2025, 4, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 4, 30).unwrap();
+        let today = NaiveDate::from_ymd_opt(2025, 4, 10).unwrap();
+        let entries = vec![spent(2025, 4, 1, 10000), spent(2025, 4, 10, 10000)];
+
+        let forecast = build(&entries, today, start, end, 50000);
+        assert_eq!(forecast.spent, "$200.00");
+        assert_eq!(forecast.linear, "$600.00");
+        // too little history for a weekday pattern, so both agree
+        assert_eq!(forecast.weighted, "$600.00");
+        // $20/day from the 1st, so $500 is reached on the 25th
+        assert_eq!(forecast.exhausted_on, NaiveDate::from_ymd_opt(2025, 4, 25));
+        assert_eq!(
+            build(&entries, today, start, end, 100000).exhausted_on,
+            None
+        );
+    }
+
+    #[test]
+    fn test_weekday_forecast() {
+        // $70 every Saturday in March, nothing otherwise
+        let mut entries: Vec<Entry> = [1, 8, 15, 22, 29]
+            .iter()
+            .map(|day| spent(2025, 3, *day, 7000))
+            .collect();
+        entries.push(spent(2025, 4, 5, 7000));
+        let start = NaiveDate::from_ymd_opt(2025, 4, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 4, 30).unwrap();
+        // a Monday, with the 12th, 19th and 26th still to come
+        let today = NaiveDate::from_ymd_opt(2025, 4, 7).unwrap();
+
+        let forecast = build(&entries, today, start, end, 25000);
+        assert_eq!(forecast.spent, "$70.00");
+        assert_eq!(forecast.weighted, "$280.00");
+        assert_eq!(forecast.linear, "$300.00");
+        assert_eq!(forecast.exhausted_on, NaiveDate::from_ymd_opt(2025, 4, 26));
+    }
+}
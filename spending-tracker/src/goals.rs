@@ -0,0 +1,148 @@
+use crate::ledger::{minor_from_f32, usd};
+use chrono::{Datelike, NaiveDate};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct GoalRequest {
+    pub name: String,
+    pub target: f32,
+    pub deadline: NaiveDate,
+}
+
+/// Money put towards the goal called `goal`, as in `save 100 vacation`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SaveRequest {
+    pub goal: String,
+    pub amount: f32,
+}
+
+/// Something being saved up for. Amounts are in minor units (cents). Savings are kept apart
+/// from spending, so they don't count against the budget.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Goal {
+    pub name: String,
+    pub target: i64,
+    pub deadline: NaiveDate,
+    #[serde(default)]
+    pub saved: i64,
+}
+
+impl From<GoalRequest> for Goal {
+    fn from(req: GoalRequest) -> Self {
+        Self {
+            name: req.name.trim().to_string(),
+            target: minor_from_f32(req.target),
+            deadline: req.deadline,
+            saved: 0,
+        }
+    }
+}
+
+impl Goal {
+    pub fn is_called(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name.trim())
+    }
+
+    pub fn remaining(&self) -> i64 {
+        (self.target - self.saved).max(0)
+    }
+
+    /// How much has to be saved each month from `today` on to reach the target by the
+    /// deadline. Whatever is left is due at once when the deadline is this month or past.
+    pub fn monthly_pace(&self, today: NaiveDate) -> i64 {
+        let months = months_between(today, self.deadline).max(1);
+        (self.remaining() + months - 1) / months
+    }
+
+    pub fn progress(&self, today: NaiveDate) -> GoalProgress {
+        GoalProgress {
+            name: self.name.clone(),
+            target: usd(self.target).to_string(),
+            saved: usd(self.saved).to_string(),
+            remaining: usd(self.remaining()).to_string(),
+            deadline: self.deadline,
+            percent: if self.target > 0 {
+                self.saved * 100 / self.target
+            } else {
+                100
+            },
+            monthly_pace: usd(self.monthly_pace(today)).to_string(),
+        }
+    }
+}
+
+/// Calendar months from `from` to `to`, counting a partly elapsed month as a whole one.
+fn months_between(from: NaiveDate, to: NaiveDate) -> i64 {
+    let months =
+        i64::from(to.year() - from.year()) * 12 + i64::from(to.month()) - i64::from(from.month());
+    if to.day() > from.day() {
+        months + 1
+    } else {
+        months
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct GoalProgress {
+    pub name: String,
+    pub target: String,
+    pub saved: String,
+    pub remaining: String,
+    pub deadline: NaiveDate,
+    pub percent: i64,
+    /// What has to be saved each month to make the deadline.
+    pub monthly_pace: String,
+}
+
+impl fmt::Display for GoalProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} of {} saved ({}%)",
+            self.name, self.saved, self.target, self.percent
+        )?;
+        if self.percent < 100 {
+            write!(
+                f,
+                ", {}/month to reach it by {}",
+                self.monthly_pace, self.deadline
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_monthly_pace() {
+        let today = NaiveDate::from_ymd_opt(2025, 10, 18).unwrap();
+        let mut goal = Goal::from(GoalRequest {
+            name: " Vacation ".to_string(),
+            target: 2000.0,
+            deadline: NaiveDate::from_ymd_opt(2026, 3, 31).unwrap(),
+        });
+        goal.saved = 20000;
+        assert!(goal.is_called("vacation"));
+        // October's remainder through March is six months
+        assert_eq!(goal.monthly_pace(today), 30000);
+        let progress = goal.progress(today);
+        assert_eq!(progress.percent, 10);
+        assert_eq!(
+            progress.to_string(),
+            "Vacation: $200.00 of $2,000.00 saved (10%), $300.00/month to reach it by 2026-03-31"
+        );
+
+        goal.deadline = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        assert_eq!(goal.monthly_pace(today), 180000);
+        goal.saved = 250000;
+        assert_eq!(goal.remaining(), 0);
+        assert_eq!(
+            goal.progress(today).to_string(),
+            "Vacation: $2,500.00 of $2,000.00 saved (125%)"
+        );
+    }
+}
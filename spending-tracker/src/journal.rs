@@ -0,0 +1,671 @@
+//! Plain-text accounting journals. Spending is booked from `Assets:Checking` to an
+//! `Expenses:` account named after its category, income from an `Income:` account named after
+//! its source. The ledger flavour reads in both ledger-cli and hledger.
+
+use crate::categories::Categories;
+use crate::currency::{self, Foreign};
+use crate::ledger::{to_minor, usd, Entry, Split};
+use crate::{normalize_tag, Category, Kind};
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use std::fmt;
+use std::fmt::Write;
+
+/// Local time of day, kept as metadata since journals only date transactions.
+const TIME_FORMAT: &str = "%H:%M:%S%.f";
+
+/// Where spending is paid from and income paid into.
+pub const FUNDING_ACCOUNT: &str = "Assets:Checking";
+
+/// The account spending on `category` is booked to, e.g. `Expenses:Home-Garden` for
+/// "Home & Garden".
+pub fn account(category: &Category) -> String {
+    format!("Expenses:{}", component(category.name()))
+}
+
+fn income_account(source: Option<&str>) -> String {
+    format!("Income:{}", component(source.unwrap_or("Other")))
+}
+
+/// An account name segment: capitalized words joined by `-`, as beancount requires.
+fn component(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    if words.is_empty() {
+        "Other".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+struct Posting {
+    account: String,
+    /// In base currency minor units, positive for money flowing into the account.
+    amount: i64,
+    /// The amount in the currency it was charged in, with the same sign as `amount`.
+    foreign: Option<Foreign>,
+}
+
+impl Posting {
+    fn render(&self) -> String {
+        let amount = format!(
+            "{} {}",
+            usd(self.amount).amount(),
+            currency::BASE.iso_alpha_code
+        );
+        match self.foreign.as_ref().and_then(Foreign::money) {
+            Some(original) => format!(
+                "{} {} @@ {}",
+                original.amount(),
+                self.foreign.as_ref().map_or("", |f| f.currency.as_str()),
+                amount.trim_start_matches('-')
+            ),
+            None => amount,
+        }
+    }
+}
+
+/// Postings for an entry. Split entries are booked in the base currency only, since the
+/// original amount isn't known per split.
+fn postings(entry: &Entry) -> Vec<Posting> {
+    let sign = if entry.kind == Kind::Refund { -1 } else { 1 };
+    let funding = Posting {
+        account: FUNDING_ACCOUNT.to_string(),
+        amount: if entry.kind == Kind::Income {
+            entry.amount
+        } else {
+            -sign * entry.amount
+        },
+        foreign: None,
+    };
+    if entry.kind == Kind::Income {
+        return vec![
+            funding,
+            Posting {
+                account: income_account(entry.source.as_deref()),
+                amount: -entry.amount,
+                foreign: entry.foreign.clone().map(|f| Foreign {
+                    amount: -f.amount,
+                    ..f
+                }),
+            },
+        ];
+    }
+    let mut postings: Vec<Posting> = if entry.splits.is_empty() {
+        vec![Posting {
+            account: account(&entry.category),
+            amount: sign * entry.amount,
+            foreign: entry.foreign.clone().map(|f| Foreign {
+                amount: sign * f.amount,
+                ..f
+            }),
+        }]
+    } else {
+        entry
+            .splits
+            .iter()
+            .map(|split| Posting {
+                account: account(&split.category),
+                amount: sign * split.amount,
+                foreign: None,
+            })
+            .collect()
+    };
+    postings.push(funding);
+    postings
+}
+
+/// What goes in the payee position: the merchant, or for income its source.
+fn payee(entry: &Entry) -> Option<&str> {
+    match entry.kind {
+        Kind::Income => entry.source.as_deref(),
+        _ => entry.merchant.as_deref(),
+    }
+}
+
+fn write_postings(out: &mut String, indent: &str, entry: &Entry) {
+    for posting in postings(entry) {
+        let _ = writeln!(out, "{indent}{:<34}  {}", posting.account, posting.render());
+    }
+}
+
+/// Renders entries as a ledger-cli/hledger journal.
+pub fn to_ledger<'e>(entries: impl IntoIterator<Item = &'e Entry>) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        let _ = write!(out, "{} *", entry.time.date_naive());
+        if let Some(payee) = payee(entry) {
+            let _ = write!(out, " {payee}");
+        }
+        if let Some(note) = &entry.note {
+            let _ = write!(out, " | {note}");
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "    ; time: {}", entry.time.format(TIME_FORMAT));
+        for tag in &entry.tags {
+            let _ = writeln!(out, "    ; {tag}:");
+        }
+        write_postings(&mut out, "    ", entry);
+        let _ = writeln!(out);
+    }
+    out
+}
+
+/// Renders entries as a beancount file, opening every account on the first entry's date.
+pub fn to_beancount<'e>(entries: impl IntoIterator<Item = &'e Entry> + Clone) -> String {
+    let mut out = format!(
+        "option \"operating_currency\" \"{}\"\n\n",
+        currency::BASE.iso_alpha_code
+    );
+    let mut accounts: Vec<String> = entries
+        .clone()
+        .into_iter()
+        .flat_map(postings)
+        .map(|posting| posting.account)
+        .collect();
+    accounts.sort();
+    accounts.dedup();
+    if let Some(opened) = entries
+        .clone()
+        .into_iter()
+        .map(|entry| entry.time.date_naive())
+        .min()
+    {
+        for account in accounts {
+            let _ = writeln!(out, "{opened} open {account}");
+        }
+        let _ = writeln!(out);
+    }
+    for entry in entries {
+        let _ = write!(
+            out,
+            "{} * \"{}\" \"{}\"",
+            entry.time.date_naive(),
+            quote(payee(entry).unwrap_or_default()),
+            quote(entry.note.as_deref().unwrap_or_default())
+        );
+        for tag in &entry.tags {
+            let _ = write!(out, " #{tag}");
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "  time: \"{}\"", entry.time.format(TIME_FORMAT));
+        write_postings(&mut out, "  ", entry);
+        let _ = writeln!(out);
+    }
+    out
+}
+
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, PartialEq)]
+pub struct JournalError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+/// A transaction as read from either journal flavour.
+#[derive(Default)]
+struct Block {
+    line: usize,
+    date: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+    payee: Option<String>,
+    note: Option<String>,
+    tags: Vec<String>,
+    postings: Vec<Posting>,
+    /// The posting left without an amount to balance the others, if any.
+    elided: Option<usize>,
+}
+
+impl Block {
+    fn error(&self, message: impl Into<String>) -> JournalError {
+        JournalError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /// The entry this transaction books, mapping account names back to categories.
+    fn entry(self, categories: &Categories) -> Result<Entry, JournalError> {
+        let date = self.date.ok_or_else(|| self.error("missing date"))?;
+        let time = Local
+            .from_local_datetime(&date.and_time(self.time.unwrap_or_default()))
+            .earliest()
+            .unwrap_or_default();
+        let mut postings = self.postings;
+        if let Some(elided) = self.elided {
+            let balance: i64 = postings.iter().map(|posting| posting.amount).sum();
+            postings[elided].amount = -balance;
+        }
+        let category = |account: &str| {
+            let name = account.trim_start_matches("Expenses:");
+            categories
+                .0
+                .iter()
+                .map(|info| &info.name)
+                .find(|category| component(category.name()) == name)
+                .cloned()
+                .unwrap_or_else(|| Category::new(&name.replace('-', " ")))
+        };
+
+        let mut entry = Entry {
+            time,
+            note: self.note.filter(|note| !note.is_empty()),
+            tags: self.tags,
+            ..Default::default()
+        };
+        let payee = self.payee.filter(|payee| !payee.is_empty());
+        if let Some(income) = postings
+            .iter()
+            .find(|posting| posting.account.starts_with("Income:"))
+        {
+            entry.kind = Kind::Income;
+            entry.amount = -income.amount;
+            entry.foreign = income.foreign.clone().map(|f| Foreign {
+                amount: -f.amount,
+                ..f
+            });
+            entry.source = payee;
+            return Ok(entry);
+        }
+
+        let expenses: Vec<&Posting> = postings
+            .iter()
+            .filter(|posting| posting.account.starts_with("Expenses:"))
+            .collect();
+        let total: i64 = expenses.iter().map(|posting| posting.amount).sum();
+        if expenses.is_empty() {
+            return Err(JournalError {
+                line: self.line,
+                message: "no Expenses or Income posting".to_string(),
+            });
+        }
+        let sign = if total < 0 { -1 } else { 1 };
+        entry.kind = if total < 0 {
+            Kind::Refund
+        } else {
+            Kind::Expense
+        };
+        entry.amount = sign * total;
+        entry.merchant = payee;
+        entry.category = category(&expenses[0].account);
+        if let [posting] = expenses.as_slice() {
+            entry.foreign = posting.foreign.clone().map(|f| Foreign {
+                amount: sign * f.amount,
+                ..f
+            });
+        } else {
+            entry.set_splits(
+                expenses
+                    .iter()
+                    .map(|posting| Split {
+                        category: category(&posting.account),
+                        amount: sign * posting.amount,
+                    })
+                    .collect(),
+            );
+        }
+        Ok(entry)
+    }
+}
+
+/// `12.50 EUR @@ 13.50 USD` or `-24.78 USD`.
+fn parse_amount(s: &str) -> Option<(i64, Option<Foreign>)> {
+    let money = |s: &str| -> Option<(Decimal, &'static iso::Currency)> {
+        let (number, code) = s.trim().split_once(char::is_whitespace)?;
+        Some((number.trim().parse().ok()?, currency::find(code)?))
+    };
+    match s.split_once("@@") {
+        Some((original, price)) => {
+            let (original, currency) = money(original)?;
+            let (price, _) = money(price)?;
+            let amount = to_minor(&Money::from_decimal(price, currency::BASE));
+            let sign = if original.is_sign_negative() { -1 } else { 1 };
+            Some((
+                sign * amount,
+                Some(Foreign {
+                    amount: to_minor(&Money::from_decimal(original, currency)),
+                    currency: currency.iso_alpha_code.to_string(),
+                }),
+            ))
+        }
+        None => {
+            let (amount, currency) = money(s)?;
+            Some((to_minor(&Money::from_decimal(amount, currency)), None))
+        }
+    }
+}
+
+/// A posting line. Its amount is `None` when left for the journal to balance.
+fn parse_posting(line: &str) -> Option<(Posting, bool)> {
+    let line = line.trim();
+    let Some((account, amount)) = line.split_once("  ") else {
+        let elided = Posting {
+            account: line.to_string(),
+            amount: 0,
+            foreign: None,
+        };
+        return (!line.contains(' ')).then_some((elided, true));
+    };
+    let (amount, foreign) = parse_amount(amount)?;
+    Some((
+        Posting {
+            account: account.trim().to_string(),
+            amount,
+            foreign,
+        },
+        false,
+    ))
+}
+
+/// Splits a journal into transactions: a dated header line followed by indented lines.
+/// Everything else, such as comments, options and `open` directives, is skipped.
+fn blocks<'s>(
+    s: &'s str,
+    mut header: impl FnMut(&mut Block, &'s str),
+    mut detail: impl FnMut(&mut Block, &'s str) -> bool,
+) -> Result<Vec<Block>, JournalError> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+    for (index, line) in s.lines().enumerate() {
+        let indented = line.starts_with(' ') || line.starts_with('\t');
+        if indented && !line.trim().is_empty() {
+            let Some(block) = current.as_mut() else {
+                continue;
+            };
+            if detail(block, line.trim()) {
+                continue;
+            }
+            match parse_posting(line) {
+                Some((posting, elided)) if !elided || block.elided.is_none() => {
+                    if elided {
+                        block.elided = Some(block.postings.len());
+                    }
+                    block.postings.push(posting);
+                }
+                _ => {
+                    return Err(JournalError {
+                        line: index + 1,
+                        message: format!("can't read posting {:?}", line.trim()),
+                    })
+                }
+            }
+            continue;
+        }
+        blocks.extend(current.take());
+        let Some((date, rest)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(date) = date.parse::<NaiveDate>() else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let rest = rest.strip_prefix(['*', '!']).unwrap_or(rest).trim_start();
+        let mut block = Block {
+            line: index + 1,
+            date: Some(date),
+            ..Default::default()
+        };
+        header(&mut block, rest);
+        current = Some(block);
+    }
+    blocks.extend(current);
+    // directives such as `open` have no postings
+    blocks.retain(|block| !block.postings.is_empty());
+    Ok(blocks)
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim().trim_matches('"'), TIME_FORMAT).ok()
+}
+
+/// Reads entries back from a ledger-cli/hledger journal written by [`to_ledger`].
+pub fn from_ledger(s: &str, categories: &Categories) -> Result<Vec<Entry>, JournalError> {
+    blocks(
+        s,
+        |block, description| {
+            let (payee, note) = match description.split_once('|') {
+                Some((payee, note)) => (payee, Some(note.trim().to_string())),
+                None => (description, None),
+            };
+            block.payee = Some(payee.trim().to_string());
+            block.note = note;
+        },
+        |block, line| {
+            let Some(comment) = line.strip_prefix(';') else {
+                return false;
+            };
+            match comment.trim().split_once(':') {
+                Some(("time", value)) => block.time = parse_time(value),
+                Some((tag, "")) => block.tags.push(normalize_tag(tag)),
+                _ => {}
+            }
+            true
+        },
+    )?
+    .into_iter()
+    .map(|block| block.entry(categories))
+    .collect()
+}
+
+/// Reads entries back from a beancount file written by [`to_beancount`].
+pub fn from_beancount(s: &str, categories: &Categories) -> Result<Vec<Entry>, JournalError> {
+    blocks(
+        s,
+        |block, header| {
+            let mut strings = Vec::new();
+            let mut rest = header;
+            while let Some(after) = rest.trim_start().strip_prefix('"') {
+                let (string, remainder) = unquote(after);
+                strings.push(string);
+                rest = remainder;
+            }
+            block.tags = rest
+                .split_whitespace()
+                .filter_map(|word| word.strip_prefix('#'))
+                .map(normalize_tag)
+                .collect();
+            // a lone string is the narration
+            if strings.len() == 1 {
+                strings.insert(0, String::new());
+            }
+            let mut strings = strings.into_iter();
+            block.payee = strings.next();
+            block.note = strings.next();
+        },
+        |block, line| match line.split_once(':') {
+            Some((key, value))
+                if key.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+                    && !key.contains(' ') =>
+            {
+                if key == "time" {
+                    block.time = parse_time(value);
+                }
+                true
+            }
+            _ => line.starts_with(';'),
+        },
+    )?
+    .into_iter()
+    .map(|block| block.entry(categories))
+    .collect()
+}
+
+/// Reads a quoted string up to its closing quote. Returns it and whatever follows.
+fn unquote(s: &str) -> (String, &str) {
+    let mut string = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    string.push(escaped);
+                }
+            }
+            '"' => return (string, &s[i + 1..]),
+            c => string.push(c),
+        }
+    }
+    (string, "")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::categories::CategoryInfo;
+    use chrono::DateTime;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                id: 1,
+                amount: 2478,
+                category: Category::Dining,
+                time: at(1, 12, 0),
+                merchant: Some("Pappy's Smokehouse".to_string()),
+                note: Some("ribs \"to go\"".to_string()),
+                tags: vec!["birthday".to_string(), "bbq".to_string()],
+                ..Default::default()
+            },
+            Entry {
+                id: 2,
+                amount: 8000,
+                category: Category::Grocery,
+                time: at(2, 9, 15),
+                merchant: Some("Costco".to_string()),
+                splits: vec![
+                    Split {
+                        category: Category::Grocery,
+                        amount: 5500,
+                    },
+                    Split {
+                        category: Category::new("Home & Garden"),
+                        amount: 2500,
+                    },
+                ],
+                ..Default::default()
+            },
+            Entry {
+                id: 3,
+                amount: 2000,
+                category: Category::Merchandise,
+                time: at(3, 17, 45),
+                kind: Kind::Refund,
+                ..Default::default()
+            },
+            Entry {
+                id: 4,
+                amount: 1350,
+                category: Category::Dining,
+                time: at(4, 20, 0),
+                foreign: Some(Foreign {
+                    amount: 1250,
+                    currency: "EUR".to_string(),
+                }),
+                ..Default::default()
+            },
+            Entry {
+                id: 5,
+                amount: 120000,
+                time: at(5, 8, 0),
+                kind: Kind::Income,
+                source: Some("salary".to_string()),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn categories() -> Categories {
+        let mut categories = Categories::default();
+        categories
+            .create(CategoryInfo {
+                name: Category::new("Home & Garden"),
+                aliases: Vec::new(),
+                archived: false,
+            })
+            .unwrap();
+        categories
+    }
+
+    /// Everything a journal keeps. Ids are reassigned on import and chats aren't exported.
+    fn same(read: &[Entry], written: &[Entry]) {
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(written) {
+            assert_eq!(
+                Entry {
+                    id: written.id,
+                    ..read.clone()
+                },
+                *written
+            );
+        }
+    }
+
+    #[test]
+    fn test_account_names() {
+        assert_eq!(account(&Category::Dining), "Expenses:Dining");
+        assert_eq!(
+            account(&Category::new("home & garden")),
+            "Expenses:Home-Garden"
+        );
+        assert_eq!(income_account(Some("side gig")), "Income:Side-Gig");
+    }
+
+    #[test]
+    fn test_ledger_round_trip() {
+        let fixture = include_str!("../fixtures/journal.ledger");
+        assert_eq!(to_ledger(&entries()), fixture);
+        same(&from_ledger(fixture, &categories()).unwrap(), &entries());
+    }
+
+    #[test]
+    fn test_beancount_round_trip() {
+        let fixture = include_str!("../fixtures/journal.beancount");
+        assert_eq!(to_beancount(&entries()), fixture);
+        same(&from_beancount(fixture, &categories()).unwrap(), &entries());
+    }
+
+    #[test]
+    fn test_hand_written_ledger() {
+        let journal =
+            "; lunch\n2025-01-06 Cafe\n    Expenses:Dining  4.50 USD\n    Assets:Checking\n";
+        let entries = from_ledger(journal, &categories()).unwrap();
+        assert_eq!(entries[0].amount, 450);
+        assert_eq!(entries[0].merchant.as_deref(), Some("Cafe"));
+
+        let journal = "2025-01-06 * Cafe\n    Expenses:Dining  four USD\n    Assets:Checking\n";
+        assert_eq!(
+            from_ledger(journal, &categories()),
+            Err(JournalError {
+                line: 2,
+                message: "can't read posting \"Expenses:Dining  four USD\"".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::currency::Foreign;
+use crate::{Category, Kind, PersonTotal, Transaction, TransactionSplit};
+use chrono::{DateTime, Local};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use serde_derive::{Deserialize, Serialize};
+
+/// A single charge, refund or deposit as the tracker stores it. Amounts are in minor units
+/// (cents) and always positive; `kind` says which way the money went.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Entry {
+    pub id: u64,
+    pub amount: i64,
+    pub category: Category,
+    pub time: DateTime<Local>,
+    #[serde(default)]
+    pub kind: Kind,
+    /// Where income came from, such as `salary`.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// The original amount when charged in a foreign currency. `amount` is the converted one.
+    #[serde(default)]
+    pub foreign: Option<Foreign>,
+    #[serde(default)]
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Telegram chat the charge was sent from, used to find what `undo` should reverse.
+    #[serde(default)]
+    pub chat_id: Option<i64>,
+    /// Telegram user who logged the entry.
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub user_name: Option<String>,
+    /// How the amount divides between categories, when it was split. `category` is then the
+    /// first split's.
+    #[serde(default)]
+    pub splits: Vec<Split>,
+    /// The statement line this entry was imported from, for matching it up on later imports.
+    #[serde(default)]
+    pub import_id: Option<String>,
+}
+
+/// The part of a split charge filed under one category, in minor units.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Split {
+    pub category: Category,
+    pub amount: i64,
+}
+
+impl Entry {
+    pub fn money(&self) -> Money<'static, iso::Currency> {
+        usd(self.amount)
+    }
+
+    /// What this entry adds to spending: refunds count against it and income not at all.
+    pub fn spending(&self) -> i64 {
+        match self.kind {
+            Kind::Expense => self.amount,
+            Kind::Refund => -self.amount,
+            Kind::Income => 0,
+        }
+    }
+
+    /// What this entry adds to spending on `category`, counting only its share when split.
+    pub fn spending_in(&self, category: &Category) -> i64 {
+        if self.splits.is_empty() {
+            return if self.category == *category {
+                self.spending()
+            } else {
+                0
+            };
+        }
+        let share: i64 = self
+            .splits
+            .iter()
+            .filter(|split| split.category == *category)
+            .map(|split| split.amount)
+            .sum();
+        self.spending().signum() * share
+    }
+
+    /// Every category this entry is filed under.
+    pub fn categories(&self) -> impl Iterator<Item = &Category> {
+        let whole = self.splits.is_empty().then_some(&self.category);
+        whole
+            .into_iter()
+            .chain(self.splits.iter().map(|split| &split.category))
+    }
+
+    /// Divides the entry between the categories in `splits`, which should add up to its
+    /// amount. A single split just sets the category.
+    pub fn set_splits(&mut self, mut splits: Vec<Split>) {
+        if let Some(first) = splits.first() {
+            self.category = first.category.clone();
+        }
+        if splits.len() < 2 {
+            splits.clear();
+        }
+        self.splits = splits;
+    }
+
+    /// Two entries describe the same charge if they came from the same statement line, or
+    /// otherwise happened at the same moment for the same amount, kind and category. Ids are
+    /// not compared since they differ between machines.
+    pub fn is_duplicate_of(&self, other: &Entry) -> bool {
+        if let (Some(id), Some(other_id)) = (&self.import_id, &other.import_id) {
+            return id == other_id;
+        }
+        self.time == other.time
+            && self.amount == other.amount
+            && self.kind == other.kind
+            && self.category == other.category
+    }
+
+    pub fn transaction(&self) -> Transaction {
+        Transaction {
+            id: self.id,
+            amount: self.money().to_string(),
+            category: self.category.to_string(),
+            time: self.time.to_string(),
+            kind: self.kind,
+            source: self.source.clone(),
+            original: self.foreign.as_ref().map(Foreign::to_string),
+            merchant: self.merchant.clone(),
+            note: self.note.clone(),
+            tags: self.tags.clone(),
+            splits: self
+                .splits
+                .iter()
+                .map(|split| TransactionSplit {
+                    category: split.category.to_string(),
+                    amount: usd(split.amount).to_string(),
+                })
+                .collect(),
+            user: self.user(),
+        }
+    }
+
+    /// The name of whoever logged the entry, or their id if no name was given.
+    pub fn user(&self) -> Option<String> {
+        self.user_name
+            .clone()
+            .or_else(|| self.user_id.map(|id| id.to_string()))
+    }
+}
+
+pub fn usd(minor: i64) -> Money<'static, iso::Currency> {
+    Money::from_minor(minor, iso::USD)
+}
+
+/// Converts a bot-supplied amount such as `24.78` into minor units.
+pub fn minor_from_f32(amount: f32) -> i64 {
+    (amount * 100.0).round() as i64
+}
+
+pub fn to_minor(money: &Money<iso::Currency>) -> i64 {
+    (money.amount() * Decimal::from(10i64.pow(money.currency().exponent)))
+        .round()
+        .to_i64()
+        .unwrap_or_default()
+}
+
+/// Spending across the given entries less refunds, optionally restricted to one category.
+/// Split entries only count their share of that category.
+pub fn total<'e>(
+    entries: impl IntoIterator<Item = &'e Entry>,
+    category: Option<&Category>,
+) -> Money<'static, iso::Currency> {
+    usd(entries
+        .into_iter()
+        .map(|entry| category.map_or(entry.spending(), |c| entry.spending_in(c)))
+        .sum())
+}
+
+/// Income across the given entries.
+pub fn income<'e>(entries: impl IntoIterator<Item = &'e Entry>) -> Money<'static, iso::Currency> {
+    usd(entries
+        .into_iter()
+        .filter(|entry| entry.kind == Kind::Income)
+        .map(|entry| entry.amount)
+        .sum())
+}
+
+/// Income less spending, i.e. how much the account grew over the given entries.
+pub fn net<'e>(
+    entries: impl IntoIterator<Item = &'e Entry> + Clone,
+) -> Money<'static, iso::Currency> {
+    income(entries.clone()) - total(entries, None)
+}
+
+/// Spending across the given entries split by who logged it, largest first. Entries nobody
+/// is recorded against are left out.
+pub fn by_person<'e>(entries: impl IntoIterator<Item = &'e Entry>) -> Vec<PersonTotal> {
+    let mut people: Vec<(i64, String, i64)> = Vec::new();
+    for entry in entries {
+        let Some(user_id) = entry.user_id else {
+            continue;
+        };
+        let name = entry.user().unwrap_or_default();
+        match people.iter_mut().find(|(id, ..)| *id == user_id) {
+            Some((_, known, total)) => {
+                *known = name;
+                *total += entry.spending();
+            }
+            None => people.push((user_id, name, entry.spending())),
+        }
+    }
+    people.sort_by_key(|(.., total)| std::cmp::Reverse(*total));
+    people
+        .into_iter()
+        .map(|(user_id, name, total)| PersonTotal {
+            user_id,
+            name,
+            total: usd(total).to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(amount: i64, category: Category, kind: Kind) -> Entry {
+        Entry {
+            amount,
+            category,
+            kind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cash_flow() {
+        let entries = vec![
+            entry(5000, Category::Merchandise, Kind::Expense),
+            entry(2000, Category::Merchandise, Kind::Refund),
+            entry(1000, Category::Dining, Kind::Expense),
+            entry(120000, Category::Other, Kind::Income),
+        ];
+        assert_eq!(total(&entries, None), usd(4000));
+        assert_eq!(total(&entries, Some(&Category::Merchandise)), usd(3000));
+        assert_eq!(income(&entries), usd(120000));
+        assert_eq!(net(&entries), usd(116000));
+    }
+
+    #[test]
+    fn test_by_person() {
+        let logged = |amount, user_id, user_name: &str| Entry {
+            amount,
+            user_id: Some(user_id),
+            user_name: Some(user_name.to_string()),
+            ..Default::default()
+        };
+        let entries = vec![
+            logged(1000, 1, "Sam"),
+            logged(5000, 2, "Alex"),
+            logged(2500, 1, "Samantha"),
+            entry(9900, Category::Other, Kind::Expense),
+        ];
+        let people = by_person(&entries);
+        assert_eq!(
+            people
+                .iter()
+                .map(|p| (p.user_id, p.name.as_str(), p.total.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(2, "Alex", "$50.00"), (1, "Samantha", "$35.00")]
+        );
+    }
+
+    #[test]
+    fn test_split_totals() {
+        let mut split = entry(8000, Category::Other, Kind::Expense);
+        split.set_splits(vec![
+            Split {
+                category: Category::Grocery,
+                amount: 5500,
+            },
+            Split {
+                category: Category::Merchandise,
+                amount: 2500,
+            },
+        ]);
+        assert_eq!(split.category, Category::Grocery);
+        let mut refund = split.clone();
+        refund.kind = Kind::Refund;
+        refund.set_splits(vec![Split {
+            category: Category::Merchandise,
+            amount: 8000,
+        }]);
+        assert!(refund.splits.is_empty());
+
+        let entries = vec![
+            split,
+            entry(1000, Category::Merchandise, Kind::Expense),
+            entry(500, Category::Grocery, Kind::Refund),
+        ];
+        assert_eq!(total(&entries, None), usd(8500));
+        assert_eq!(total(&entries, Some(&Category::Grocery)), usd(5000));
+        assert_eq!(total(&entries, Some(&Category::Merchandise)), usd(3500));
+        assert_eq!(total(&entries, Some(&Category::Dining)), usd(0));
+        assert_eq!(
+            entries[0].categories().collect::<Vec<_>>(),
+            vec![&Category::Grocery, &Category::Merchandise]
+        );
+    }
+}
@@ -1,70 +1,614 @@
+pub mod alert;
+pub mod categories;
+pub mod currency;
+pub mod export;
+pub mod forecast;
+pub mod goals;
+pub mod journal;
+pub mod ledger;
+pub mod period;
+pub mod recurring;
+pub mod report;
+pub mod statement;
+pub mod storage;
+
+use categories::CategoryError;
+use chrono::NaiveDate;
+use currency::ConversionError;
 use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
 
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
-pub enum Category {
-    Dining,
-    Grocery,
-    Travel,
-    Merchandise,
-    Entertainment,
-    Other,
+/// Request header naming the ledger a request is for. Without it the default ledger is used.
+pub const LEDGER_HEADER: &str = "x-ledger";
+/// Request header carrying the Telegram chat a request came from, which picks whatever
+/// ledger that chat was pointed at.
+pub const CHAT_HEADER: &str = "x-chat-id";
+/// Name of the ledger used when a request doesn't ask for another.
+pub const DEFAULT_LEDGER: &str = "default";
+
+/// A spending category, identified by its name. The built-in ones below always exist; any
+/// others are created through `/categories`. Serialized as the bare name, so ledgers written
+/// when categories were a fixed enum still load.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct Category(Cow<'static, str>);
+
+#[allow(non_upper_case_globals)]
+impl Category {
+    pub const Dining: Self = Self(Cow::Borrowed("Dining"));
+    pub const Grocery: Self = Self(Cow::Borrowed("Grocery"));
+    pub const Travel: Self = Self(Cow::Borrowed("Travel"));
+    pub const Merchandise: Self = Self(Cow::Borrowed("Merchandise"));
+    pub const Entertainment: Self = Self(Cow::Borrowed("Entertainment"));
+    /// Where anything without a category goes. It can't be renamed, merged or archived.
+    pub const Other: Self = Self(Cow::Borrowed("Other"));
+
+    pub fn new(name: &str) -> Self {
+        Self(Cow::Owned(name.trim().to_string()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for Category {
+    fn default() -> Self {
+        Self::Other
+    }
 }
 
 impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Which way money moved. Expenses count against the budget, refunds give it back and
+/// income only shows up in cash flow.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum Kind {
+    #[default]
+    Expense,
+    Income,
+    Refund,
+}
+
+impl fmt::Display for Kind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
             "{}",
             match *self {
-                Self::Dining => "Dining",
-                Self::Grocery => "Grocery",
-                Self::Travel => "Travel",
-                Self::Merchandise => "Merchandise",
-                Self::Entertainment => "Entertainment",
-                Self::Other => "Other",
+                Self::Expense => "expense",
+                Self::Income => "income",
+                Self::Refund => "refund",
             }
         )
     }
 }
 
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Default)]
 pub struct SpentRequest {
     pub amount: f32,
     pub category: Option<Category>,
+    #[serde(default)]
+    pub kind: Kind,
+    /// Where income came from, such as `salary`.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// ISO code of the currency `amount` is in. Defaults to the ledger's base currency.
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub chat_id: Option<i64>,
+    /// Telegram user who sent the charge, so a shared ledger can tell who spent what.
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub user_name: Option<String>,
+    /// Divides `amount` between several categories instead of filing it under `category`.
+    /// The splits have to add up to `amount`.
+    #[serde(default)]
+    pub splits: Vec<SplitRequest>,
+}
+
+/// One category's share of a split charge, in the same currency as the charge.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct SplitRequest {
+    pub category: Category,
+    pub amount: f32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SplitError {
+    Category(CategoryError),
+    Conversion(ConversionError),
+    NotPositive(Category),
+    /// The splits add up to the first amount where the charge was for the second.
+    Mismatch(String, String),
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Category(e) => write!(f, "{e}"),
+            Self::Conversion(e) => write!(f, "{e}"),
+            Self::NotPositive(category) => write!(f, "the {category} split has to be positive"),
+            Self::Mismatch(splits, total) => {
+                write!(f, "splits add up to {splits} but the total is {total}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+/// Tags are kept lower case and without their leading `#`.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Oldest,
+    Newest,
+    Largest,
+    Smallest,
+}
+
+/// Narrows which transactions `GET /spent` totals up and lists. Without a date range only
+/// the current period is searched.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct SpentQuery {
+    /// Only transactions carrying this tag.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Only transactions whose merchant contains this text, ignoring case.
+    #[serde(default)]
+    pub merchant: Option<String>,
+    /// First day to include.
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    /// Last day to include.
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Smallest amount to include, in the base currency.
+    #[serde(default)]
+    pub min: Option<f32>,
+    /// Largest amount to include, in the base currency.
+    #[serde(default)]
+    pub max: Option<f32>,
+    /// Text to look for in merchants, notes and income sources, ignoring case.
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: Option<Sort>,
+    /// How many transactions to list. The totals still cover every match.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// How many matching transactions to skip before listing.
+    #[serde(default)]
+    pub offset: Option<usize>,
+}
+
+impl SpentQuery {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the query reaches back past the current period.
+    pub fn has_date_range(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    pub fn matches(&self, entry: &ledger::Entry) -> bool {
+        let date = entry.time.date_naive();
+        let contains = |field: &Option<String>, text: &str| {
+            field
+                .as_ref()
+                .is_some_and(|f| f.to_lowercase().contains(&text.to_lowercase()))
+        };
+        self.tag
+            .as_ref()
+            .is_none_or(|tag| entry.tags.contains(&normalize_tag(tag)))
+            && self
+                .merchant
+                .as_ref()
+                .is_none_or(|merchant| contains(&entry.merchant, merchant))
+            && self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date <= to)
+            && self.category.as_ref().is_none_or(|category| {
+                entry
+                    .categories()
+                    .any(|c| c.name().eq_ignore_ascii_case(category))
+            })
+            && self
+                .min
+                .is_none_or(|min| entry.amount >= ledger::minor_from_f32(min))
+            && self
+                .max
+                .is_none_or(|max| entry.amount <= ledger::minor_from_f32(max))
+            && self.q.as_ref().is_none_or(|q| {
+                contains(&entry.merchant, q)
+                    || contains(&entry.note, q)
+                    || contains(&entry.source, q)
+            })
+    }
+
+    /// Filters, sorts and pages `entries`. Returns every match, for totals, and the page.
+    pub fn apply<'e>(
+        &self,
+        entries: impl IntoIterator<Item = &'e ledger::Entry>,
+    ) -> (Vec<&'e ledger::Entry>, Vec<&'e ledger::Entry>) {
+        let mut matched: Vec<&ledger::Entry> = entries
+            .into_iter()
+            .filter(|entry| self.matches(entry))
+            .collect();
+        match self.sort.unwrap_or_default() {
+            Sort::Oldest => matched.sort_by_key(|entry| entry.time),
+            Sort::Newest => matched.sort_by_key(|entry| std::cmp::Reverse(entry.time)),
+            Sort::Largest => matched.sort_by_key(|entry| std::cmp::Reverse(entry.amount)),
+            Sort::Smallest => matched.sort_by_key(|entry| entry.amount),
+        }
+        let page = matched
+            .iter()
+            .copied()
+            .skip(self.offset.unwrap_or(0))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        (matched, page)
+    }
+}
+
+/// Changes to an existing transaction. Fields left out are kept as they are.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct SpentPatch {
+    pub amount: Option<f32>,
+    pub category: Option<Category>,
+    #[serde(default)]
+    pub kind: Option<Kind>,
+    #[serde(default)]
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// New splits, which have to add up to the new amount. Setting `category` un-splits.
+    #[serde(default)]
+    pub splits: Option<Vec<SplitRequest>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct UndoRequest {
+    pub chat_id: Option<i64>,
+    /// Only undo this user's transactions, for chats several people log to.
+    #[serde(default)]
+    pub user_id: Option<i64>,
+}
+
+impl UndoRequest {
+    /// Whether undo may remove `entry`. Entries logged before they recorded who added them
+    /// can be undone by anyone in the chat.
+    pub fn matches(&self, entry: &ledger::Entry) -> bool {
+        (self.chat_id.is_none() || entry.chat_id == self.chat_id)
+            && (self.user_id.is_none() || entry.user_id.is_none() || entry.user_id == self.user_id)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UndoResponse {
+    pub removed: Transaction,
+    pub total: String,
+}
+
+impl fmt::Display for UndoResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "removed: {} {}\ntotal: {}",
+            self.removed.amount, self.removed.category, self.total
+        )
+    }
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct SpentResponse {
     pub total: String,
+    /// Budget status of the category just charged, if that category has its own budget.
+    #[serde(default)]
+    pub category: Option<CategoryBudget>,
 }
 
 impl fmt::Display for SpentResponse {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "total: {}", self.total)
+        write!(f, "total: {}", self.total)?;
+        if let Some(category) = &self.category {
+            write!(f, "\n{}", category)?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CategoryBudget {
+    pub category: Category,
+    pub budget: String,
+    pub spent: String,
+    pub remaining: String,
+}
+
+impl fmt::Display for CategoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} of {} left",
+            self.category, self.remaining, self.budget
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct BudgetResponse {
+    pub budget: String,
+    pub remaining: String,
+    pub categories: Vec<CategoryBudget>,
+}
+
+impl fmt::Display for BudgetResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "budget: {}\nremaining: {}", self.budget, self.remaining)?;
+        for category in &self.categories {
+            write!(f, "\n{}", category)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct Transaction {
+    #[serde(default)]
+    pub id: u64,
     pub amount: String,
     pub category: String,
     pub time: String,
+    #[serde(default)]
+    pub kind: Kind,
+    #[serde(default)]
+    pub source: Option<String>,
+    /// The amount as charged, when that was in a foreign currency.
+    #[serde(default)]
+    pub original: Option<String>,
+    #[serde(default)]
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub splits: Vec<TransactionSplit>,
+    /// Who logged it, when that is known.
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TransactionSplit {
+    pub category: String,
+    pub amount: String,
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "• #{} {}", self.id, self.amount)?;
+        if self.splits.is_empty() {
+            write!(f, " {}", self.category)?;
+        }
+        for (i, split) in self.splits.iter().enumerate() {
+            let joiner = if i == 0 { "" } else { " +" };
+            write!(f, "{joiner} {} {}", split.category, split.amount)?;
+        }
+        if let Some(original) = &self.original {
+            write!(f, " ({original})")?;
+        }
+        if let Some(merchant) = &self.merchant {
+            write!(f, " at {merchant}")?;
+        }
+        for tag in &self.tags {
+            write!(f, " #{tag}")?;
+        }
+        if self.kind != Kind::Expense {
+            write!(f, " ({})", self.kind)?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, " \"{note}\"")?;
+        }
+        if let Some(user) = &self.user {
+            write!(f, " by {user}")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct SpentTotalResponse {
     pub budget: String,
     pub total: String,
+    /// Budget left over from the previous period, when rollover is enabled.
+    #[serde(default)]
+    pub carried_over: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<CategoryBudget>,
+    /// Income received this period.
+    #[serde(default)]
+    pub income: String,
+    /// Income less net spending for the period.
+    #[serde(default)]
+    pub net: String,
+    /// How many transactions matched, which can be more than are listed when paging.
+    #[serde(default)]
+    pub matched: usize,
+    /// Spending by each person who logged any, for shared ledgers.
+    #[serde(default)]
+    pub people: Vec<PersonTotal>,
+    /// Where the whole period's spending is heading. Left out for filtered queries.
+    #[serde(default)]
+    pub forecast: Option<forecast::Forecast>,
     pub transactions: Vec<Transaction>,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PersonTotal {
+    pub user_id: i64,
+    pub name: String,
+    pub total: String,
+}
+
 impl fmt::Display for SpentTotalResponse {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "budget: {}\ntotal: {}\ntransactions: {:?}",
-            self.budget, self.total, self.transactions
-        )
+        write!(f, "budget: {}", self.budget)?;
+        if let Some(carried_over) = &self.carried_over {
+            write!(f, " (+{} carried over)", carried_over)?;
+        }
+        write!(f, "\ntotal: {}", self.total)?;
+        for category in &self.categories {
+            write!(f, "\n{}", category)?;
+        }
+        write!(f, "\nincome: {}\nnet: {}", self.income, self.net)?;
+        for person in &self.people {
+            write!(f, "\nby {}: {}", person.name, person.total)?;
+        }
+        if let Some(forecast) = &self.forecast {
+            write!(f, "\n{forecast}")?;
+        }
+        for transaction in &self.transactions {
+            write!(f, "\n{}", transaction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Points a chat at a named ledger, creating the ledger if it doesn't exist yet.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct LedgerRequest {
+    pub name: String,
+    #[serde(default)]
+    pub chat_id: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct LedgerInfo {
+    pub name: String,
+    /// Chats that log to this ledger. The default ledger also gets every chat not listed
+    /// under another.
+    pub chats: Vec<i64>,
+}
+
+impl fmt::Display for LedgerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ledger: {}", self.name)?;
+        if self.chats.len() > 1 {
+            write!(f, " (shared by {} chats)", self.chats.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// The name a ledger is known by, since names are matched ignoring case.
+pub fn ledger_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Ledger names end up in file names, so they are kept to letters, digits, `-` and `_`.
+pub fn is_ledger_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn entry(day: u32, amount: i64, category: Category, merchant: &str) -> ledger::Entry {
+        ledger::Entry {
+            id: u64::from(day),
+            amount,
+            category,
+            time: Local.with_ymd_and_hms(2025, 3, day, 12, 0, 0).unwrap(),
+            merchant: Some(merchant.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spent_query() {
+        let entries = vec![
+            entry(1, 1200, Category::Dining, "Pappy's"),
+            entry(5, 9000, Category::Grocery, "Schnucks"),
+            entry(9, 3000, Category::Dining, "Pho Grand"),
+            entry(12, 500, Category::Dining, "Kaldi's"),
+        ];
+        let query = SpentQuery {
+            from: NaiveDate::from_ymd_opt(2025, 3, 2),
+            to: NaiveDate::from_ymd_opt(2025, 3, 12),
+            category: Some("dining".to_string()),
+            sort: Some(Sort::Largest),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let (matched, page) = query.apply(&entries);
+        assert_eq!(matched.len(), 2);
+        assert_eq!(page.iter().map(|e| e.id).collect::<Vec<_>>(), vec![9]);
+
+        let query = SpentQuery {
+            q: Some("GRAND".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(query.apply(&entries).0[0].id, 9);
+
+        let query = SpentQuery {
+            min: Some(10.0),
+            max: Some(50.0),
+            sort: Some(Sort::Newest),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let (matched, page) = query.apply(&entries);
+        assert_eq!(matched.len(), 2);
+        assert_eq!(page[0].id, 1);
+    }
+
+    #[test]
+    fn test_undo_matches() {
+        let undo = UndoRequest {
+            chat_id: Some(1),
+            user_id: Some(10),
+        };
+        let logged = |chat_id, user_id| ledger::Entry {
+            chat_id,
+            user_id,
+            ..Default::default()
+        };
+        assert!(undo.matches(&logged(Some(1), Some(10))));
+        assert!(!undo.matches(&logged(Some(1), Some(11))));
+        assert!(!undo.matches(&logged(Some(2), Some(10))));
+        // logged before entries recorded their user
+        assert!(undo.matches(&logged(Some(1), None)));
+        assert!(!undo.matches(&logged(Some(2), None)));
     }
 }
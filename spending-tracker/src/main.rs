@@ -1,18 +1,43 @@
-use axum::http::{header, HeaderValue, Uri};
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderValue, Uri};
 use axum::response::Html;
 use axum::{
-    extract::{Json, State},
+    extract::{Json, Path, Query, State},
     http::{Method, StatusCode},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Router,
 };
-use chrono::Local;
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
 use clap::{arg, command};
 use rust_embed::RustEmbed;
 use rusty_money::{iso, Money};
-use spending_tracker::{Category, SpentRequest, SpentResponse, SpentTotalResponse, Transaction};
-use std::sync::{Arc, RwLock};
+use spending_tracker::alert::{self, Alert, AlertConfig, Outgoing};
+use spending_tracker::categories::{
+    Categories, CategoryError, CategoryInfo, CategoryPatch, MergeRequest,
+};
+use spending_tracker::currency::{self, Rates};
+use spending_tracker::export::{self, ExportQuery, ImportResponse, Record};
+use spending_tracker::forecast::{self, Forecast};
+use spending_tracker::goals::{Goal, GoalProgress, GoalRequest, SaveRequest};
+use spending_tracker::journal::{self, JournalError};
+use spending_tracker::ledger::{self, minor_from_f32, to_minor, usd, Entry, Split};
+use spending_tracker::period::{Period, PeriodConfig, PeriodDetail, PeriodSummary};
+use spending_tracker::recurring::{Recurring, RecurringRequest, RecurringResponse};
+use spending_tracker::report::{self, ReportQuery, TrendQuery};
+use spending_tracker::statement::{self, Rule, StatementLine};
+use spending_tracker::storage::{self, JsonFile, Memory, Snapshot, Storage};
+use spending_tracker::{
+    is_ledger_name, ledger_name, normalize_tag, BudgetResponse, Category, CategoryBudget, Kind,
+    LedgerInfo, LedgerRequest, SpentPatch, SpentQuery, SpentRequest, SpentResponse,
+    SpentTotalResponse, SplitError, SplitRequest, UndoRequest, UndoResponse, CHAT_HEADER,
+    DEFAULT_LEDGER, LEDGER_HEADER,
+};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use tower_http::cors::CorsLayer;
 
 #[derive(RustEmbed)]
@@ -22,22 +47,728 @@ struct Asset;
 #[derive(Clone)]
 struct AppState<'a> {
     state: Arc<RwLock<StateTotal<'a>>>,
+    storage: Arc<dyn Storage>,
+    periods: PeriodConfig,
+    alerts: Arc<AlertConfig>,
+    /// Alerts being sent right now, so they aren't sent twice while that happens.
+    sending: Arc<Mutex<Vec<Alert>>>,
 }
 
+#[derive(Clone)]
 struct StateTotal<'a> {
     budget: Money<'a, iso::Currency>,
-    total: Money<'a, iso::Currency>,
-    transactions: Vec<Transaction>,
+    category_budgets: BTreeMap<Category, Money<'a, iso::Currency>>,
+    transactions: Vec<Entry>,
+    next_id: u64,
+    period_start: NaiveDate,
+    carried_over: Money<'a, iso::Currency>,
+    periods: Vec<Period>,
+    rules: Vec<Rule>,
+    recurring: Vec<Recurring>,
+    next_recurring_id: u64,
+    history: Vec<Entry>,
+    alerts_sent: Vec<Alert>,
+    rates: Rates,
+    categories: Categories,
+    goals: Vec<Goal>,
+    ledgers: Vec<String>,
+    chat_ledgers: BTreeMap<i64, String>,
 }
 
-impl AppState<'_> {
-    fn new() -> Self {
+impl<'a> StateTotal<'a> {
+    fn new(period_start: NaiveDate) -> Self {
         Self {
-            state: Arc::new(RwLock::new(StateTotal {
-                budget: Money::from_major(500, iso::USD),
-                total: Money::from_minor(0, iso::USD),
-                transactions: Vec::new(),
-            })),
+            budget: usd(50_000),
+            category_budgets: BTreeMap::new(),
+            transactions: Vec::new(),
+            next_id: 1,
+            period_start,
+            carried_over: usd(0),
+            periods: Vec::new(),
+            rules: Vec::new(),
+            recurring: Vec::new(),
+            next_recurring_id: 1,
+            history: Vec::new(),
+            alerts_sent: Vec::new(),
+            rates: Rates::new(),
+            categories: Categories::default(),
+            goals: Vec::new(),
+            ledgers: Vec::new(),
+            chat_ledgers: BTreeMap::new(),
+        }
+    }
+
+    fn from_snapshot(snapshot: Snapshot, period_start: NaiveDate) -> Self {
+        let mut state = Self {
+            budget: usd(snapshot.budget),
+            category_budgets: snapshot
+                .category_budgets
+                .into_iter()
+                .map(|(category, minor)| (category, usd(minor)))
+                .collect(),
+            next_id: snapshot.next_id.max(1),
+            transactions: snapshot.transactions,
+            period_start: snapshot.period_start.unwrap_or(period_start),
+            carried_over: usd(snapshot.carried_over),
+            periods: snapshot.periods,
+            rules: snapshot.rules,
+            recurring: snapshot.recurring,
+            next_recurring_id: snapshot.next_recurring_id.max(1),
+            history: snapshot.history,
+            alerts_sent: snapshot.alerts_sent,
+            rates: snapshot.rates,
+            categories: snapshot.categories,
+            goals: snapshot.goals,
+            ledgers: snapshot.ledgers,
+            chat_ledgers: snapshot.chat_ledgers,
+        };
+        // files from before transactions had ids
+        for entry in state.transactions.iter_mut().filter(|entry| entry.id == 0) {
+            entry.id = state.next_id;
+            state.next_id += 1;
+        }
+        state.find_period_entries();
+        state
+    }
+
+    /// Works out which history entries belong to periods closed before periods kept track of
+    /// them. Each period archived whatever was dated up to its end and not archived earlier.
+    fn find_period_entries(&mut self) {
+        let mut claimed: Vec<u64> = self
+            .periods
+            .iter()
+            .filter_map(|period| period.entries.as_ref())
+            .flatten()
+            .copied()
+            .collect();
+        for period in self.periods.iter_mut().filter(|p| p.entries.is_none()) {
+            let entries: Vec<u64> = self
+                .history
+                .iter()
+                .filter(|entry| entry.time.date_naive() <= period.end)
+                .map(|entry| entry.id)
+                .filter(|id| !claimed.contains(id))
+                .collect();
+            claimed.extend(&entries);
+            period.entries = Some(entries);
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            budget: to_minor(&self.budget),
+            category_budgets: self
+                .category_budgets
+                .iter()
+                .map(|(category, money)| (category.clone(), to_minor(money)))
+                .collect(),
+            transactions: self.transactions.clone(),
+            next_id: self.next_id,
+            period_start: Some(self.period_start),
+            carried_over: to_minor(&self.carried_over),
+            periods: self.periods.clone(),
+            rules: self.rules.clone(),
+            recurring: self.recurring.clone(),
+            next_recurring_id: self.next_recurring_id,
+            history: self.history.clone(),
+            alerts_sent: self.alerts_sent.clone(),
+            rates: self.rates.clone(),
+            categories: self.categories.clone(),
+            goals: self.goals.clone(),
+            ledgers: self.ledgers.clone(),
+            chat_ledgers: self.chat_ledgers.clone(),
+        }
+    }
+
+    /// The budget for the current period, including anything carried over.
+    fn available(&self) -> Money<'a, iso::Currency> {
+        self.budget + self.carried_over
+    }
+
+    fn total(&self) -> Money<'static, iso::Currency> {
+        ledger::total(&self.transactions, None)
+    }
+
+    fn remaining(&self) -> Money<'a, iso::Currency> {
+        self.available() - self.total()
+    }
+
+    /// Adds an entry to the current period under the next free id.
+    fn push(&mut self, mut entry: Entry) {
+        entry.id = self.next_id;
+        self.next_id += 1;
+        self.transactions.push(entry);
+    }
+
+    /// Adds entries brought in from elsewhere, skipping any the ledger already had. Entries
+    /// dated before the current period go into the history of closed ones so they don't count
+    /// against this period's budget. Returns how many were added.
+    fn import(&mut self, entries: impl IntoIterator<Item = Entry>) -> usize {
+        // entries added below get ids from here on, and are never duplicates of each other
+        let first_imported = self.next_id;
+        let mut imported = 0;
+        for mut entry in entries {
+            if self
+                .history
+                .iter()
+                .chain(&self.transactions)
+                .filter(|existing| existing.id < first_imported)
+                .any(|existing| entry.is_duplicate_of(existing))
+            {
+                continue;
+            }
+            self.categories.ensure(&entry.category);
+            entry.id = self.next_id;
+            self.next_id += 1;
+            if entry.time.date_naive() < self.period_start {
+                self.history.push(entry);
+            } else {
+                self.transactions.push(entry);
+            }
+            imported += 1;
+        }
+        self.history.sort_by_key(|entry| entry.time);
+        self.transactions.sort_by_key(|entry| entry.time);
+        imported
+    }
+
+    /// Everything kept in the ledger that `query` covers, oldest first.
+    fn exported(&self, query: &ExportQuery) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self
+            .history
+            .iter()
+            .chain(&self.transactions)
+            .filter(|entry| query.includes(entry))
+            .collect();
+        entries.sort_by_key(|entry| entry.time);
+        entries
+    }
+
+    /// Posts recurring expenses that fell due and closes periods that have ended, in that
+    /// order so late postings still land in the period they belong to. Returns whether
+    /// anything changed.
+    fn catch_up(&mut self, today: NaiveDate, config: &PeriodConfig) -> bool {
+        let posted = self.post_due_recurring(today);
+        self.close_elapsed_periods(today, config) || posted
+    }
+
+    fn post_due_recurring(&mut self, today: NaiveDate) -> bool {
+        let mut due = Vec::new();
+        for recurring in self.recurring.iter_mut() {
+            while recurring.next_due <= today {
+                due.push(Entry {
+                    id: 0,
+                    amount: recurring.amount,
+                    category: recurring.category.clone(),
+                    time: Local
+                        .from_local_datetime(&recurring.next_due.and_time(NaiveTime::default()))
+                        .earliest()
+                        .unwrap_or_else(Local::now),
+                    ..Default::default()
+                });
+                recurring.next_due = recurring.schedule.next_due(recurring.next_due);
+            }
+        }
+        let posted = !due.is_empty();
+        due.sort_by_key(|entry| entry.time);
+        for entry in due {
+            self.push(entry);
+        }
+        self.transactions.sort_by_key(|entry| entry.time);
+        posted
+    }
+
+    /// The category spending asking for `requested` is filed under, or Other if it didn't ask.
+    fn category(&self, requested: Option<&Category>) -> Result<Category, CategoryError> {
+        match requested {
+            Some(category) => self
+                .categories
+                .resolve(category.name())
+                .ok_or_else(|| CategoryError::NotFound(category.to_string())),
+            None => Ok(Category::Other),
+        }
+    }
+
+    /// Resolves and converts the splits of a charge in the currency `code` that came to
+    /// `original` minor units of that currency and `total` of the base currency. Whatever
+    /// conversion rounding leaves over goes to the last split.
+    fn splits(
+        &self,
+        requested: &[SplitRequest],
+        code: &str,
+        original: i64,
+        total: i64,
+    ) -> Result<Vec<Split>, SplitError> {
+        let mut splits = Vec::new();
+        let mut requested_total = 0;
+        for split in requested {
+            let category = self
+                .category(Some(&split.category))
+                .map_err(SplitError::Category)?;
+            if split.amount <= 0.0 {
+                return Err(SplitError::NotPositive(category));
+            }
+            let (amount, foreign) = currency::convert(split.amount, code, &self.rates)
+                .map_err(SplitError::Conversion)?;
+            requested_total += foreign.map_or(amount, |foreign| foreign.amount);
+            splits.push(Split { category, amount });
+        }
+        if !splits.is_empty() && requested_total != original {
+            let currency = currency::find(code).unwrap_or(currency::BASE);
+            return Err(SplitError::Mismatch(
+                Money::from_minor(requested_total, currency).to_string(),
+                Money::from_minor(original, currency).to_string(),
+            ));
+        }
+        let converted: i64 = splits.iter().map(|split| split.amount).sum();
+        if let Some(last) = splits.last_mut() {
+            last.amount += total - converted;
+        }
+        Ok(splits)
+    }
+
+    /// Files everything under `from` under `to` instead, after a rename or merge. Budgets of
+    /// merged categories are added together.
+    fn recategorize(&mut self, from: &Category, to: &Category) {
+        for entry in self.transactions.iter_mut().chain(self.history.iter_mut()) {
+            if entry.category == *from {
+                entry.category = to.clone();
+            }
+            for split in &mut entry.splits {
+                if split.category == *from {
+                    split.category = to.clone();
+                }
+            }
+        }
+        for recurring in &mut self.recurring {
+            if recurring.category == *from {
+                recurring.category = to.clone();
+            }
+        }
+        for rule in &mut self.rules {
+            if rule.category == *from {
+                rule.category = to.clone();
+            }
+        }
+        for alert in &mut self.alerts_sent {
+            if alert.category.as_ref() == Some(from) {
+                alert.category = Some(to.clone());
+            }
+        }
+        if let Some(budget) = self.category_budgets.remove(from) {
+            let budget = self
+                .category_budgets
+                .get(to)
+                .map_or(budget, |existing| *existing + budget);
+            self.category_budgets.insert(to.clone(), budget);
+        }
+    }
+
+    fn add_recurring(&mut self, req: RecurringRequest, today: NaiveDate) -> Recurring {
+        let recurring = Recurring {
+            id: self.next_recurring_id,
+            amount: minor_from_f32(req.amount),
+            category: req.category.unwrap_or(Category::Other),
+            next_due: req.schedule.first_due(req.start.unwrap_or(today)),
+            schedule: req.schedule,
+        };
+        self.next_recurring_id += 1;
+        self.recurring.push(recurring.clone());
+        recurring
+    }
+
+    /// Archives every period that ended on or before `today`. Returns whether any closed.
+    fn close_elapsed_periods(&mut self, today: NaiveDate, config: &PeriodConfig) -> bool {
+        let mut closed = false;
+        loop {
+            let next_start = config.next_start(self.period_start);
+            if today < next_start {
+                return closed;
+            }
+            self.close_period(next_start.pred_opt().unwrap_or(next_start), config);
+            self.period_start = next_start;
+            closed = true;
+        }
+    }
+
+    /// Closes the current period early, archiving everything spent so far as a period ending
+    /// `today`. The next one starts tomorrow and runs to the usual boundary, so the following
+    /// periods close on the configured start day again.
+    fn reset(&mut self, today: NaiveDate, config: &PeriodConfig) {
+        self.catch_up(today, config);
+        self.close_period(today, config);
+        let defaults = StateTotal::new(self.period_start);
+        self.budget = defaults.budget;
+        self.category_budgets = defaults.category_budgets;
+        self.carried_over = defaults.carried_over;
+        self.period_start = today.succ_opt().unwrap_or(today);
+    }
+
+    /// Archives the current period up to and including `end`. Anything dated later stays.
+    fn close_period(&mut self, end: NaiveDate, config: &PeriodConfig) {
+        let (archived, kept) = std::mem::take(&mut self.transactions)
+            .into_iter()
+            .partition(|entry| entry.time.date_naive() <= end);
+        self.transactions = archived;
+        let available = self.available();
+        let total = self.total();
+        self.periods.push(Period {
+            id: self.periods.len() + 1,
+            start: self.period_start,
+            end,
+            budget: available.to_string(),
+            total: total.to_string(),
+            categories: self.category_budget_list(),
+            entries: Some(self.transactions.iter().map(|entry| entry.id).collect()),
+        });
+        self.history.append(&mut self.transactions);
+        self.transactions = kept;
+        self.alerts_sent.clear();
+        self.carried_over = if config.rollover && available > total {
+            available - total
+        } else {
+            usd(0)
+        };
+    }
+
+    /// Budget status for a category, or `None` if it has no budget of its own.
+    fn category_budget(&self, category: &Category) -> Option<CategoryBudget> {
+        let budget = *self.category_budgets.get(category)?;
+        let spent = ledger::total(&self.transactions, Some(category));
+        Some(CategoryBudget {
+            category: category.clone(),
+            budget: budget.to_string(),
+            spent: spent.to_string(),
+            remaining: (budget - spent).to_string(),
+        })
+    }
+
+    /// Alerts for budget thresholds spending has reached, overall and for each category with
+    /// a budget, leaving out ones already sent this period or being `sending`.
+    fn check_alerts(&self, thresholds: &[u32], sending: &[Alert]) -> Vec<Outgoing> {
+        let mut scopes = vec![(None, self.available(), self.total())];
+        for (category, budget) in &self.category_budgets {
+            scopes.push((
+                Some(category.clone()),
+                *budget,
+                ledger::total(&self.transactions, Some(category)),
+            ));
+        }
+        let mut outgoing = Vec::new();
+        for (scope, budget, spent) in scopes {
+            let sent: Vec<u32> = self
+                .alerts_sent
+                .iter()
+                .chain(sending)
+                .filter(|alert| alert.category == scope)
+                .map(|alert| alert.threshold)
+                .collect();
+            let crossed = alert::crossed(thresholds, to_minor(&spent), to_minor(&budget), &sent);
+            let Some(highest) = crossed.last() else {
+                continue;
+            };
+            let name = scope.as_ref().map_or(String::new(), |c| format!("{c} "));
+            outgoing.push(Outgoing {
+                message: format!(
+                    "Budget alert: {highest}% of the {budget} {name}budget used ({spent} spent)"
+                ),
+                alerts: crossed
+                    .into_iter()
+                    .map(|threshold| Alert {
+                        category: scope.clone(),
+                        threshold,
+                    })
+                    .collect(),
+            });
+        }
+        outgoing
+    }
+
+    fn category_budget_list(&self) -> Vec<CategoryBudget> {
+        self.category_budgets
+            .keys()
+            .filter_map(|category| self.category_budget(category))
+            .collect()
+    }
+
+    fn spent_response(&self, category: &Category) -> SpentResponse {
+        SpentResponse {
+            total: self.remaining().to_string(),
+            category: self.category_budget(category),
+        }
+    }
+
+    fn forecast(&self, today: NaiveDate, config: &PeriodConfig) -> Forecast {
+        let next_start = config.next_start(self.period_start);
+        forecast::build(
+            self.history.iter().chain(&self.transactions),
+            today,
+            self.period_start,
+            next_start.pred_opt().unwrap_or(next_start),
+            to_minor(&self.available()),
+        )
+    }
+
+    /// Totals over the transactions `query` matches, listing the requested page of them.
+    /// Closed periods are only searched when the query has a date range.
+    fn total_response(&self, query: &SpentQuery) -> SpentTotalResponse {
+        let mut query = query.clone();
+        if let Some(info) = query
+            .category
+            .as_deref()
+            .and_then(|c| self.categories.find(c))
+        {
+            query.category = Some(info.name.to_string());
+        }
+        let (entries, page) = if query.has_date_range() {
+            query.apply(self.history.iter().chain(&self.transactions))
+        } else {
+            query.apply(&self.transactions)
+        };
+        let category = query.category.as_deref().map(Category::new);
+        SpentTotalResponse {
+            budget: self.budget.to_string(),
+            total: ledger::total(entries.iter().copied(), category.as_ref()).to_string(),
+            carried_over: (!self.carried_over.is_zero()).then(|| self.carried_over.to_string()),
+            categories: self.category_budget_list(),
+            income: ledger::income(entries.iter().copied()).to_string(),
+            net: ledger::net(entries.iter().copied()).to_string(),
+            matched: entries.len(),
+            people: ledger::by_person(entries.iter().copied()),
+            forecast: None,
+            transactions: page.into_iter().map(Entry::transaction).collect(),
+        }
+    }
+}
+
+impl AppState<'_> {
+    fn new(
+        storage: Arc<dyn Storage>,
+        periods: PeriodConfig,
+        alerts: Arc<AlertConfig>,
+    ) -> std::io::Result<Self> {
+        let period_start = periods.start_of(Local::now().date_naive());
+        let state = match storage.load()? {
+            Some(snapshot) => StateTotal::from_snapshot(snapshot, period_start),
+            None => StateTotal::new(period_start),
+        };
+        Ok(Self {
+            state: Arc::new(RwLock::new(state)),
+            storage,
+            periods,
+            alerts,
+            sending: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Writes the given state through to storage so it survives a restart.
+    fn save(&self, state: &StateTotal) -> std::io::Result<()> {
+        self.storage.save(&state.snapshot())
+    }
+
+    /// Saves a change made to `state`, putting back how it was `before` if that fails so what
+    /// is in memory never gets ahead of what is on disk.
+    fn commit<'s>(
+        &self,
+        state: &mut StateTotal<'s>,
+        before: StateTotal<'s>,
+    ) -> std::io::Result<()> {
+        let saved = self.save(state);
+        if saved.is_err() {
+            *state = before;
+        }
+        saved
+    }
+}
+
+impl AppState<'static> {
+    /// Sends alerts for any budget thresholds `state` has reached. Call it once the change
+    /// that reached them has been saved.
+    fn announce(&self, state: &StateTotal) {
+        let (Some(url), Ok(mut sending)) = (&self.alerts.url, self.sending.lock()) else {
+            return;
+        };
+        let outgoing = state.check_alerts(&self.alerts.thresholds, &sending);
+        if outgoing.is_empty() {
+            return;
+        }
+        for message in &outgoing {
+            sending.extend(message.alerts.iter().cloned());
+        }
+        tokio::spawn(self.clone().deliver(url.clone(), outgoing));
+    }
+
+    /// Posts alerts to notification-service, recording each as sent once it has gone through.
+    /// One that fails is tried again the next time spending is checked.
+    async fn deliver(self, url: String, outgoing: Vec<Outgoing>) {
+        let client = reqwest::Client::new();
+        for message in outgoing {
+            let delivered = client
+                .post(&url)
+                .body(message.message)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .is_ok();
+            if delivered {
+                if let Ok(mut state) = self.state.write() {
+                    state.alerts_sent.extend(message.alerts.iter().cloned());
+                    if let Err(e) = self.save(&state) {
+                        eprintln!("couldn't save sent budget alerts: {e}");
+                    }
+                }
+            }
+            if let Ok(mut sending) = self.sending.lock() {
+                sending.retain(|alert| !message.alerts.contains(alert));
+            }
+        }
+    }
+}
+
+/// Every ledger the tracker keeps. Requests go to the default one unless they name another in
+/// the `x-ledger` header or come from a chat pointed at one, given in `x-chat-id`.
+#[derive(Clone)]
+struct Household {
+    default: AppState<'static>,
+    ledgers: Arc<RwLock<BTreeMap<String, AppState<'static>>>>,
+    /// Where the default ledger is kept. The others are stored alongside it.
+    data: Option<PathBuf>,
+}
+
+impl Household {
+    fn new(
+        data: Option<PathBuf>,
+        periods: PeriodConfig,
+        alerts: AlertConfig,
+    ) -> std::io::Result<Self> {
+        let storage: Arc<dyn Storage> = match &data {
+            Some(path) => Arc::new(JsonFile::new(path)),
+            None => Arc::new(Memory),
+        };
+        let household = Self {
+            default: AppState::new(storage, periods, Arc::new(alerts))?,
+            ledgers: Arc::new(RwLock::new(BTreeMap::new())),
+            data,
+        };
+        let names = match household.default.state.read() {
+            Ok(state) => state.ledgers.clone(),
+            Err(_) => Vec::new(),
+        };
+        for name in names {
+            household.open(&name)?;
+        }
+        // ledgers opened early missed categories brought in by later ones
+        household.share(&household.default, None)?;
+        Ok(household)
+    }
+
+    /// The ledger called `name`, if there is one.
+    fn ledger(&self, name: &str) -> Option<AppState<'static>> {
+        if name == DEFAULT_LEDGER {
+            return Some(self.default.clone());
+        }
+        self.ledgers.read().ok()?.get(name).cloned()
+    }
+
+    /// The ledger called `name`, loading or creating it if needed. It takes on the household's
+    /// categories and rates, adding any of its own the household didn't have yet.
+    fn open(&self, name: &str) -> std::io::Result<AppState<'static>> {
+        if let Some(ledger) = self.ledger(name) {
+            return Ok(ledger);
+        }
+        let storage: Arc<dyn Storage> = match &self.data {
+            Some(path) => Arc::new(JsonFile::new(storage::ledger_path(path, name))),
+            None => Arc::new(Memory),
+        };
+        let ledger = AppState::new(storage, self.default.periods, self.default.alerts.clone())?;
+        if let (Ok(mut shared), Ok(mut state)) = (self.default.state.write(), ledger.state.write())
+        {
+            shared.categories.absorb(&state.categories);
+            for (code, rate) in &state.rates {
+                shared.rates.entry(code.clone()).or_insert(*rate);
+            }
+            state.categories = shared.categories.clone();
+            state.rates = shared.rates.clone();
+        }
+        if let Ok(mut ledgers) = self.ledgers.write() {
+            ledgers.insert(name.to_string(), ledger.clone());
+        }
+        Ok(ledger)
+    }
+
+    /// Copies the categories and rates of `from` to every other ledger, since the whole
+    /// household files spending under the same categories. Spending is moved along when a
+    /// category was `renamed` or merged into another. Don't hold a lock on `from` while calling.
+    fn share(
+        &self,
+        from: &AppState<'static>,
+        renamed: Option<(Category, Category)>,
+    ) -> std::io::Result<()> {
+        let (categories, rates) = match from.state.read() {
+            Ok(state) => (state.categories.clone(), state.rates.clone()),
+            Err(_) => return Ok(()),
+        };
+        for ledger in self.all() {
+            if Arc::ptr_eq(&ledger.state, &from.state) {
+                continue;
+            }
+            if let Ok(mut state) = ledger.state.write() {
+                if state.categories == categories && state.rates == rates && renamed.is_none() {
+                    continue;
+                }
+                let before = state.clone();
+                state.categories = categories.clone();
+                state.rates = rates.clone();
+                if let Some((old, new)) = &renamed {
+                    state.recategorize(old, new);
+                }
+                ledger.commit(&mut state, before)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The ledger spending from `chat_id` goes to.
+    fn for_chat(&self, chat_id: i64) -> AppState<'static> {
+        let name = match self.default.state.read() {
+            Ok(state) => state.chat_ledgers.get(&chat_id).cloned(),
+            Err(_) => None,
+        };
+        name.and_then(|name| self.ledger(&name))
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    fn all(&self) -> Vec<AppState<'static>> {
+        let mut all = vec![self.default.clone()];
+        if let Ok(ledgers) = self.ledgers.read() {
+            all.extend(ledgers.values().cloned());
+        }
+        all
+    }
+}
+
+impl FromRequestParts<Household> for AppState<'static> {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        household: &Household,
+    ) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        if let Some(name) = header(LEDGER_HEADER).map(ledger_name) {
+            return household.ledger(&name).ok_or_else(|| {
+                (StatusCode::NOT_FOUND, format!("No ledger called {name}")).into_response()
+            });
+        }
+        match header(CHAT_HEADER).map(str::parse) {
+            Some(Ok(chat_id)) => Ok(household.for_chat(chat_id)),
+            Some(Err(_)) => Err((StatusCode::BAD_REQUEST, "Invalid chat id").into_response()),
+            None => Ok(household.default.clone()),
         }
     }
 }
@@ -46,16 +777,88 @@ impl AppState<'_> {
 async fn main() {
     let cmd = command!()
         .arg(arg!( -p --port [port] "port number for webserver").required(false))
+        .arg(arg!( -d --data [path] "file to persist spending data in").required(false))
+        .arg(
+            arg!(--"start-day" [day] "day of the month budget periods begin on")
+                .required(false)
+                .value_parser(clap::value_parser!(u32)),
+        )
+        .arg(arg!(--rollover "carry unspent budget into the next period"))
+        .arg(
+            arg!(--notifications [url] "notification-service url to send budget alerts to")
+                .required(false),
+        )
+        .arg(
+            arg!(--"alert-thresholds" [percentages] "budget percentages that trigger an alert")
+                .required(false),
+        )
         .get_matches();
 
     let default_port = "8001".to_string();
     let port = cmd.get_one::<String>("port").unwrap_or(&default_port);
 
-    let state = AppState::new();
+    let periods = PeriodConfig::new(
+        cmd.get_one::<u32>("start-day").copied().unwrap_or(1),
+        cmd.get_flag("rollover"),
+    );
+    let mut alerts = AlertConfig {
+        url: cmd
+            .get_one::<String>("notifications")
+            .map(|base_url| format!("{base_url}/notifications")),
+        ..Default::default()
+    };
+    if let Some(thresholds) = cmd.get_one::<String>("alert-thresholds") {
+        alerts.thresholds = AlertConfig::parse_thresholds(thresholds);
+    }
+    let household = match Household::new(
+        cmd.get_one::<String>("data").map(PathBuf::from),
+        periods,
+        alerts,
+    ) {
+        Ok(household) => household,
+        Err(e) => {
+            eprintln!("couldn't load spending data: {e}");
+            std::process::exit(1);
+        }
+    };
+    tokio::spawn(catch_up(household.clone()));
+
     let app = Router::new()
-        .route("/budget", post(set_budget))
+        .route("/budget", post(set_budget).get(budgets))
         .route("/spent", post(spent).get(spent_total))
+        .route("/spent/{id}", delete(delete_spent).patch(edit_spent))
+        .route("/undo", post(undo))
         .route("/reset", get(reset))
+        .route("/export.csv", get(export_csv))
+        .route("/export.json", get(export_json))
+        .route("/export/ledger", get(export_ledger))
+        .route("/export/beancount", get(export_beancount))
+        .route("/import", post(import))
+        .route("/import/ofx", post(import_ofx))
+        .route("/import/qif", post(import_qif))
+        .route("/import/ledger", post(import_ledger))
+        .route("/import/beancount", post(import_beancount))
+        .route("/import/rules", get(get_rules).put(set_rules))
+        .route("/recurring", get(list_recurring).post(add_recurring))
+        .route(
+            "/recurring/{id}",
+            get(get_recurring)
+                .put(update_recurring)
+                .delete(delete_recurring),
+        )
+        .route("/categories", get(list_categories).post(create_category))
+        .route("/categories/{name}", patch(update_category))
+        .route("/categories/{name}/merge", post(merge_category))
+        .route("/rates", get(get_rates).post(set_rates))
+        .route("/report", get(get_report))
+        .route("/report/trend", get(get_trend))
+        .route("/forecast", get(get_forecast))
+        .route("/goals", get(list_goals).post(set_goal))
+        .route("/goals/{name}", delete(delete_goal))
+        .route("/savings", post(save))
+        .route("/ledgers", get(list_ledgers).post(join_ledger))
+        .route("/periods", get(list_periods))
+        .route("/periods/{id}", get(get_period))
         .route("/dist/{*file}", get(static_handler))
         .route("/", get(index))
         .layer(
@@ -65,10 +868,16 @@ async fn main() {
                         .parse::<HeaderValue>()
                         .unwrap(),
                 )
-                .allow_methods([Method::GET, Method::POST]),
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ]),
         )
         .fallback_service(get(not_found))
-        .with_state(state);
+        .with_state(household);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}"))
         .await
@@ -78,66 +887,965 @@ async fn main() {
         .expect("Failed to start webserver. Port already in use?");
 }
 
-async fn spent(State(state): State<AppState<'_>>, Json(req): Json<SpentRequest>) -> Response {
-    if let Ok(mut state) = state.state.write() {
-        let add = Money::from_minor((req.amount * 100.0).round() as i64, iso::USD);
-        state.total += add.clone();
-        state.transactions.push(Transaction {
-            amount: add.to_string(),
-            category: req.category.unwrap_or(Category::Other).to_string(),
-            time: Local::now().to_string(),
-        });
-        return Json(SpentResponse {
-            total: (state.budget.clone() - state.total.clone()).to_string(),
+async fn spent(app_state: AppState<'static>, Json(req): Json<SpentRequest>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let category = match state.category(req.category.as_ref()) {
+            Ok(category) => category,
+            Err(e) => return category_error(e),
+        };
+        let code = req
+            .currency
+            .as_deref()
+            .unwrap_or(currency::BASE.iso_alpha_code);
+        let (amount, foreign) = match currency::convert(req.amount, code, &state.rates) {
+            Ok(converted) => converted,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        };
+        let original = foreign.as_ref().map_or(amount, |foreign| foreign.amount);
+        let splits = match state.splits(&req.splits, code, original, amount) {
+            Ok(splits) => splits,
+            Err(e) => return split_error(e),
+        };
+        let mut entry = Entry {
+            id: 0,
+            amount,
+            category,
+            time: Local::now(),
+            kind: req.kind,
+            source: req.source,
+            foreign,
+            merchant: req.merchant,
+            note: req.note,
+            tags: req.tags.iter().map(|tag| normalize_tag(tag)).collect(),
+            chat_id: req.chat_id,
+            user_id: req.user_id,
+            user_name: req.user_name,
+            ..Default::default()
+        };
+        if !splits.is_empty() {
+            entry.set_splits(splits);
+        }
+        let category = entry.category.clone();
+        // only once the request checks out, so a rejected one changes nothing
+        state.catch_up(Local::now().date_naive(), &app_state.periods);
+        state.push(entry);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        app_state.announce(&state);
+        if req.kind == Kind::Income {
+            return Json(SpentResponse {
+                total: state.remaining().to_string(),
+                category: None,
+            })
+            .into_response();
+        }
+        return Json(state.spent_response(&category)).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn delete_spent(app_state: AppState<'static>, Path(id): Path<u64>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let Some(index) = state.transactions.iter().position(|entry| entry.id == id) else {
+            return (StatusCode::NOT_FOUND, "No such transaction").into_response();
+        };
+        let removed = state.transactions.remove(index);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(state.spent_response(&removed.category)).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn edit_spent(
+    app_state: AppState<'static>,
+    Path(id): Path<u64>,
+    Json(req): Json<SpentPatch>,
+) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let category = match req.category.as_ref().map(|c| state.category(Some(c))) {
+            Some(Err(e)) => return category_error(e),
+            Some(Ok(category)) => Some(category),
+            None => None,
+        };
+        let Some(existing) = state.transactions.iter().find(|entry| entry.id == id) else {
+            return (StatusCode::NOT_FOUND, "No such transaction").into_response();
+        };
+        let amount = req.amount.map_or(existing.amount, minor_from_f32);
+        let splits = match &req.splits {
+            Some(splits) => {
+                match state.splits(splits, currency::BASE.iso_alpha_code, amount, amount) {
+                    Ok(splits) => Some(splits),
+                    Err(e) => return split_error(e),
+                }
+            }
+            None if category.is_none()
+                && amount != existing.amount
+                && !existing.splits.is_empty() =>
+            {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    "Change the splits along with the amount",
+                )
+                    .into_response();
+            }
+            None => None,
+        };
+        let Some(entry) = state.transactions.iter_mut().find(|entry| entry.id == id) else {
+            return (StatusCode::NOT_FOUND, "No such transaction").into_response();
+        };
+        if req.amount.is_some() {
+            entry.amount = amount;
+            entry.foreign = None;
+        }
+        if let Some(category) = category {
+            entry.category = category;
+            entry.splits.clear();
+        }
+        if let Some(splits) = splits {
+            entry.set_splits(splits);
+        }
+        if let Some(kind) = req.kind {
+            entry.kind = kind;
+        }
+        // a blank merchant or note clears it
+        if let Some(merchant) = req.merchant {
+            entry.merchant = Some(merchant).filter(|merchant| !merchant.trim().is_empty());
+        }
+        if let Some(note) = req.note {
+            entry.note = Some(note).filter(|note| !note.trim().is_empty());
+        }
+        if let Some(tags) = req.tags {
+            entry.tags = tags.iter().map(|tag| normalize_tag(tag)).collect();
+        }
+        let category = entry.category.clone();
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        app_state.announce(&state);
+        return Json(state.spent_response(&category)).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Removes the most recent transaction sent from the given chat, or from anywhere if no chat
+/// is given.
+async fn undo(app_state: AppState<'static>, Json(req): Json<UndoRequest>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let Some(index) = state
+            .transactions
+            .iter()
+            .rposition(|entry| req.matches(entry))
+        else {
+            return (StatusCode::NOT_FOUND, "Nothing to undo").into_response();
+        };
+        let removed = state.transactions.remove(index);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(UndoResponse {
+            removed: removed.transaction(),
+            total: state.remaining().to_string(),
         })
         .into_response();
     }
     StatusCode::INTERNAL_SERVER_ERROR.into_response()
 }
 
-async fn spent_total(State(app_state): State<AppState<'_>>) -> Response {
+async fn spent_total(app_state: AppState<'static>, Query(query): Query<SpentQuery>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        let mut response = state.total_response(&query);
+        if query.is_empty() {
+            response.forecast = Some(state.forecast(Local::now().date_naive(), &app_state.periods));
+        }
+        return Json(response).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn get_forecast(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(state.forecast(Local::now().date_naive(), &app_state.periods)).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Closes the current period by hand and starts over with the default budgets.
+async fn reset(app_state: AppState<'static>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        state.reset(Local::now().date_naive(), &app_state.periods);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(state.total_response(&SpentQuery::default())).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn set_budget(app_state: AppState<'static>, Json(req): Json<SpentRequest>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let budget = usd(minor_from_f32(req.amount));
+        let category = match req.category.as_ref().map(|c| state.category(Some(c))) {
+            Some(Err(e)) => return category_error(e),
+            Some(Ok(category)) => Some(category),
+            None => None,
+        };
+        // a new budget gets its own alerts
+        state.alerts_sent.retain(|alert| alert.category != category);
+        match category {
+            // a zero budget for a category removes it, falling back to the overall cap
+            Some(category) if budget.is_zero() => {
+                state.category_budgets.remove(&category);
+            }
+            Some(category) => {
+                state.category_budgets.insert(category, budget);
+            }
+            None => state.budget = budget,
+        }
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(state.total_response(&SpentQuery::default())).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn budgets(app_state: AppState<'static>) -> Response {
     if let Ok(state) = app_state.state.read() {
-        return Json(SpentTotalResponse {
+        return Json(BudgetResponse {
             budget: state.budget.to_string(),
-            total: state.total.to_string(),
-            transactions: state.transactions.clone(),
+            remaining: state.remaining().to_string(),
+            categories: state.category_budget_list(),
         })
         .into_response();
     }
     StatusCode::INTERNAL_SERVER_ERROR.into_response()
 }
 
-async fn reset(State(app_state): State<AppState<'_>>) -> Response {
-    if let Ok(mut state) = app_state.state.write() {
-        state.budget = Money::from_major(500, iso::USD);
-        state.total = Money::from_minor(0, iso::USD);
-        state.transactions = Vec::new();
-        return Json(SpentTotalResponse {
-            budget: state.budget.clone().to_string(),
-            total: state.total.clone().to_string(),
-            transactions: Vec::new(),
-        })
+async fn export_csv(app_state: AppState<'static>, Query(query): Query<ExportQuery>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        if let Ok(csv) = export::to_csv(state.exported(&query)) {
+            return (
+                [
+                    (header::CONTENT_TYPE, "text/csv"),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"spending.csv\"",
+                    ),
+                ],
+                csv,
+            )
+                .into_response();
+        }
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn export_json(app_state: AppState<'static>, Query(query): Query<ExportQuery>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(
+            state
+                .exported(&query)
+                .into_iter()
+                .map(Record::from)
+                .collect::<Vec<_>>(),
+        )
         .into_response();
     }
     StatusCode::INTERNAL_SERVER_ERROR.into_response()
 }
 
-async fn set_budget(
-    State(app_state): State<AppState<'_>>,
-    Json(req): Json<SpentRequest>,
+async fn export_ledger(app_state: AppState<'static>, Query(query): Query<ExportQuery>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return journal_download(
+            journal::to_ledger(state.exported(&query)),
+            "spending.ledger",
+        );
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn export_beancount(
+    app_state: AppState<'static>,
+    Query(query): Query<ExportQuery>,
 ) -> Response {
-    if let Ok(mut state) = app_state.state.write() {
-        state.budget = Money::from_minor((req.amount * 100.0).round() as i64, iso::USD);
-        return Json(SpentTotalResponse {
-            budget: state.budget.clone().to_string(),
-            total: state.total.clone().to_string(),
-            transactions: state.transactions.clone(),
-        })
+    if let Ok(state) = app_state.state.read() {
+        return journal_download(
+            journal::to_beancount(state.exported(&query)),
+            "spending.beancount",
+        );
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+fn journal_download(journal: String, filename: &str) -> Response {
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        journal,
+    )
+        .into_response()
+}
+
+/// Accepts a ledger in the format produced by `/export.csv` or `/export.json`, chosen by the
+/// request's content type. Records already present are skipped, and ones from before the
+/// current period are filed with the closed periods.
+async fn import(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("csv"));
+    let records: Vec<Record> = if is_csv {
+        match export::from_csv(&body) {
+            Ok(records) => records,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        }
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(records) => records,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        }
+    };
+
+    let imported = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            let imported = state.import(records.iter().map(Record::entry));
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            app_state.announce(&state);
+            imported
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    // imports can bring in new categories
+    if household.share(&app_state, None).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(ImportResponse {
+        imported,
+        duplicates: records.len() - imported,
+        skipped: 0,
+    })
+    .into_response()
+}
+
+async fn import_ofx(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    body: String,
+) -> Response {
+    import_statement(household, app_state, statement::parse_ofx(&body))
+}
+
+async fn import_qif(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    body: String,
+) -> Response {
+    import_statement(household, app_state, statement::parse_qif(&body))
+}
+
+async fn import_ledger(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    body: String,
+) -> Response {
+    import_journal(household, app_state, &body, journal::from_ledger)
+}
+
+async fn import_beancount(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    body: String,
+) -> Response {
+    import_journal(household, app_state, &body, journal::from_beancount)
+}
+
+/// Imports a journal written by one of the journal exports, e.g. to restore from books kept
+/// elsewhere. Entries the ledger already has are skipped.
+fn import_journal(
+    household: Household,
+    app_state: AppState<'static>,
+    journal: &str,
+    read: fn(&str, &Categories) -> Result<Vec<Entry>, JournalError>,
+) -> Response {
+    let entries = match app_state.state.read() {
+        Ok(state) => read(journal, &state.categories),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let total = entries.len();
+    let imported = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            let imported = state.import(entries);
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            app_state.announce(&state);
+            imported
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, None).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(ImportResponse {
+        imported,
+        duplicates: total - imported,
+        skipped: 0,
+    })
+    .into_response()
+}
+
+fn import_statement(
+    household: Household,
+    app_state: AppState<'static>,
+    lines: Result<Vec<StatementLine>, statement::ParseError>,
+) -> Response {
+    let lines = match lines {
+        Ok(lines) => lines,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let (spending, imported) = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            let entries: Vec<Entry> = lines
+                .iter()
+                .filter_map(|line| line.entry(&state.rules))
+                .collect();
+            let spending = entries.len();
+            let imported = state.import(entries);
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            app_state.announce(&state);
+            (spending, imported)
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, None).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(ImportResponse {
+        imported,
+        duplicates: spending - imported,
+        skipped: lines.len() - spending,
+    })
+    .into_response()
+}
+
+async fn get_rates(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(state.rates.clone()).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Adds or replaces exchange rates, given as base currency units per unit of each currency.
+async fn set_rates(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    Json(rates): Json<Rates>,
+) -> Response {
+    let mut updated = Rates::new();
+    for (code, rate) in rates {
+        let Some(currency) = currency::find(&code) else {
+            return (StatusCode::BAD_REQUEST, format!("unknown currency {code}")).into_response();
+        };
+        if rate.is_sign_negative() || rate.is_zero() {
+            return (StatusCode::BAD_REQUEST, format!("bad rate for {code}")).into_response();
+        }
+        updated.insert(currency.iso_alpha_code.to_string(), rate);
+    }
+    let rates = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            state.rates.extend(updated);
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            state.rates.clone()
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, None).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(rates).into_response()
+}
+
+fn category_error(e: CategoryError) -> Response {
+    let status = match e {
+        CategoryError::NotFound(_) => StatusCode::NOT_FOUND,
+        CategoryError::Exists(_) => StatusCode::CONFLICT,
+        CategoryError::Reserved | CategoryError::Unnamed => StatusCode::BAD_REQUEST,
+    };
+    (status, e.to_string()).into_response()
+}
+
+fn split_error(e: SplitError) -> Response {
+    match e {
+        SplitError::Category(e) => category_error(e),
+        e => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+async fn list_categories(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(state.categories.clone()).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn create_category(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    Json(info): Json<CategoryInfo>,
+) -> Response {
+    let categories = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            if let Err(e) = state.categories.create(info) {
+                return category_error(e);
+            }
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            state.categories.clone()
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, None).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(categories).into_response()
+}
+
+async fn update_category(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    Path(name): Path<String>,
+    Json(patch): Json<CategoryPatch>,
+) -> Response {
+    let (categories, renamed) = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            let (old, new) = match state.categories.update(&name, patch) {
+                Ok(renamed) => renamed,
+                Err(e) => return category_error(e),
+            };
+            state.recategorize(&old, &new);
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            (state.categories.clone(), (old, new))
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, Some(renamed)).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(categories).into_response()
+}
+
+async fn merge_category(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    Path(name): Path<String>,
+    Json(req): Json<MergeRequest>,
+) -> Response {
+    let (categories, renamed) = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            let (from, into) = match state.categories.merge(&name, &req.into) {
+                Ok(renamed) => renamed,
+                Err(e) => return category_error(e),
+            };
+            state.recategorize(&from, &into);
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            (state.categories.clone(), (from, into))
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, Some(renamed)).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(categories).into_response()
+}
+
+async fn get_rules(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(state.rules.clone()).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Replaces the payee rules used to categorize imported statements. Earlier rules win.
+async fn set_rules(
+    State(household): State<Household>,
+    app_state: AppState<'static>,
+    Json(rules): Json<Vec<Rule>>,
+) -> Response {
+    let rules = match app_state.state.write() {
+        Ok(mut state) => {
+            let before = state.clone();
+            for rule in &rules {
+                state.categories.ensure(&rule.category);
+            }
+            state.rules = rules;
+            if app_state.commit(&mut state, before).is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            state.rules.clone()
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if household.share(&app_state, None).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    Json(rules).into_response()
+}
+
+async fn get_report(app_state: AppState<'static>, Query(query): Query<ReportQuery>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        let today = Local::now().date_naive();
+        let mut report = report::build(
+            query.period,
+            state.history.iter().chain(&state.transactions),
+            today,
+            &app_state.periods,
+            to_minor(&state.available()),
+        );
+        report.goals = state
+            .goals
+            .iter()
+            .map(|goal| goal.progress(today))
+            .collect();
+        return Json(report).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn get_trend(app_state: AppState<'static>, Query(query): Query<TrendQuery>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(report::trend(
+            query.period,
+            state.history.iter().chain(&state.transactions),
+            Local::now().date_naive(),
+            &app_state.periods,
+            query.count.unwrap_or(report::TREND_PERIODS),
+        ))
         .into_response();
     }
     StatusCode::INTERNAL_SERVER_ERROR.into_response()
 }
 
+async fn list_periods(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(
+            state
+                .periods
+                .iter()
+                .map(PeriodSummary::from)
+                .collect::<Vec<_>>(),
+        )
+        .into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn get_period(app_state: AppState<'static>, Path(id): Path<usize>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return match state.periods.iter().find(|period| period.id == id) {
+            Some(period) => Json(PeriodDetail::new(period, &state.history)).into_response(),
+            None => (StatusCode::NOT_FOUND, "No such period").into_response(),
+        };
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn list_recurring(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(RecurringResponse(state.recurring.clone())).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn add_recurring(
+    app_state: AppState<'static>,
+    Json(mut req): Json<RecurringRequest>,
+) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        match state.category(req.category.as_ref()) {
+            Ok(category) => req.category = Some(category),
+            Err(e) => return category_error(e),
+        }
+        let recurring = state.add_recurring(req, Local::now().date_naive());
+        state.catch_up(Local::now().date_naive(), &app_state.periods);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        app_state.announce(&state);
+        return Json(recurring).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn get_recurring(app_state: AppState<'static>, Path(id): Path<u64>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return match state.recurring.iter().find(|recurring| recurring.id == id) {
+            Some(recurring) => Json(recurring.clone()).into_response(),
+            None => (StatusCode::NOT_FOUND, "No such recurring expense").into_response(),
+        };
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn update_recurring(
+    app_state: AppState<'static>,
+    Path(id): Path<u64>,
+    Json(req): Json<RecurringRequest>,
+) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let today = Local::now().date_naive();
+        let category = match state.category(req.category.as_ref()) {
+            Ok(category) => category,
+            Err(e) => return category_error(e),
+        };
+        let Some(recurring) = state.recurring.iter_mut().find(|r| r.id == id) else {
+            return (StatusCode::NOT_FOUND, "No such recurring expense").into_response();
+        };
+        recurring.amount = minor_from_f32(req.amount);
+        recurring.category = category;
+        recurring.next_due = req.schedule.first_due(req.start.unwrap_or(today));
+        recurring.schedule = req.schedule;
+        let recurring = recurring.clone();
+        state.catch_up(today, &app_state.periods);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        app_state.announce(&state);
+        return Json(recurring).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn delete_recurring(app_state: AppState<'static>, Path(id): Path<u64>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let Some(index) = state.recurring.iter().position(|r| r.id == id) else {
+            return (StatusCode::NOT_FOUND, "No such recurring expense").into_response();
+        };
+        state.recurring.remove(index);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(RecurringResponse(state.recurring.clone())).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// How far along each goal is as of today, and what it still needs each month.
+fn goal_progress(goals: &[Goal]) -> Vec<GoalProgress> {
+    let today = Local::now().date_naive();
+    goals.iter().map(|goal| goal.progress(today)).collect()
+}
+
+async fn list_goals(app_state: AppState<'static>) -> Response {
+    if let Ok(state) = app_state.state.read() {
+        return Json(goal_progress(&state.goals)).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Adds a goal, or changes the target and deadline of the one with the same name while
+/// keeping what was already saved.
+async fn set_goal(app_state: AppState<'static>, Json(req): Json<GoalRequest>) -> Response {
+    if req.name.trim().is_empty() || req.target <= 0.0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            "A goal needs a name and a positive target",
+        )
+            .into_response();
+    }
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let mut goal = Goal::from(req);
+        if let Some(existing) = state.goals.iter_mut().find(|g| g.is_called(&goal.name)) {
+            goal.saved = existing.saved;
+            *existing = goal.clone();
+        } else {
+            state.goals.push(goal.clone());
+        }
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(goal.progress(Local::now().date_naive())).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn delete_goal(app_state: AppState<'static>, Path(name): Path<String>) -> Response {
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let Some(index) = state.goals.iter().position(|goal| goal.is_called(&name)) else {
+            return (StatusCode::NOT_FOUND, "No such goal").into_response();
+        };
+        state.goals.remove(index);
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(goal_progress(&state.goals)).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Puts money towards a goal. It doesn't count as spending.
+async fn save(app_state: AppState<'static>, Json(req): Json<SaveRequest>) -> Response {
+    if req.amount <= 0.0 {
+        return (StatusCode::BAD_REQUEST, "Savings have to be positive").into_response();
+    }
+    if let Ok(mut state) = app_state.state.write() {
+        let before = state.clone();
+        let Some(goal) = state
+            .goals
+            .iter_mut()
+            .find(|goal| goal.is_called(&req.goal))
+        else {
+            return (StatusCode::NOT_FOUND, "No such goal").into_response();
+        };
+        goal.saved += minor_from_f32(req.amount);
+        let progress = goal.progress(Local::now().date_naive());
+        if app_state.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        return Json(progress).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+async fn list_ledgers(State(household): State<Household>) -> Response {
+    if let Ok(state) = household.default.state.read() {
+        let chats = |name: &str| {
+            state
+                .chat_ledgers
+                .iter()
+                .filter(|(_, ledger)| *ledger == name)
+                .map(|(chat, _)| *chat)
+                .collect()
+        };
+        let ledgers: Vec<LedgerInfo> = std::iter::once(DEFAULT_LEDGER)
+            .chain(state.ledgers.iter().map(String::as_str))
+            .map(|name| LedgerInfo {
+                name: name.to_string(),
+                chats: chats(name),
+            })
+            .collect();
+        return Json(ledgers).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Creates the named ledger if it's new and points the given chat at it.
+async fn join_ledger(
+    State(household): State<Household>,
+    Json(req): Json<LedgerRequest>,
+) -> Response {
+    let name = ledger_name(&req.name);
+    if !is_ledger_name(&name) {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Ledger names can only use letters, digits, - and _",
+        )
+            .into_response();
+    }
+    let Ok(ledger) = household.open(&name) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    if let Ok(state) = ledger.state.read() {
+        if ledger.save(&state).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+    if let Ok(mut state) = household.default.state.write() {
+        let before = state.clone();
+        if name != DEFAULT_LEDGER && !state.ledgers.contains(&name) {
+            state.ledgers.push(name.clone());
+        }
+        if let Some(chat_id) = req.chat_id {
+            if name == DEFAULT_LEDGER {
+                state.chat_ledgers.remove(&chat_id);
+            } else {
+                state.chat_ledgers.insert(chat_id, name.clone());
+            }
+        }
+        if household.default.commit(&mut state, before).is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        let chats = state
+            .chat_ledgers
+            .iter()
+            .filter(|(_, ledger)| **ledger == name)
+            .map(|(chat, _)| *chat)
+            .collect();
+        return Json(LedgerInfo { name, chats }).into_response();
+    }
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+/// Posts recurring expenses and closes budget periods as their dates pass, even if nothing is
+/// being spent.
+async fn catch_up(household: Household) {
+    const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+    loop {
+        for app_state in household.all() {
+            if let Ok(mut state) = app_state.state.write() {
+                let before = state.clone();
+                if state.catch_up(Local::now().date_naive(), &app_state.periods) {
+                    // put back and try again on the next check
+                    if let Err(e) = app_state.commit(&mut state, before) {
+                        eprintln!("couldn't save spending data: {e}");
+                    }
+                }
+                // also retries alerts that couldn't be delivered before
+                app_state.announce(&state);
+            }
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
 async fn index() -> Response {
     static_handler("/index.html".parse::<Uri>().unwrap())
         .await
@@ -176,3 +1884,285 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use spending_tracker::recurring::Schedule;
+
+    fn date(m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, m, d).unwrap()
+    }
+
+    fn spent(m: u32, d: u32, amount: i64) -> Entry {
+        Entry {
+            amount,
+            category: Category::Dining,
+            time: Local.with_ymd_and_hms(2025, m, d, 12, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    /// A ledger whose current period ended long ago, so catching up would close it.
+    fn overdue_ledger() -> AppState<'static> {
+        let app_state = AppState::new(
+            Arc::new(Memory),
+            PeriodConfig::default(),
+            Arc::new(AlertConfig::default()),
+        )
+        .unwrap();
+        *app_state.state.write().unwrap() = StateTotal::new(date(1, 1));
+        app_state
+    }
+
+    #[tokio::test]
+    async fn test_rejected_spending_changes_nothing() {
+        let app_state = overdue_ledger();
+        let before = app_state.state.read().unwrap().snapshot();
+        let req = SpentRequest {
+            amount: 5.0,
+            category: Some(Category::new("Nonsense")),
+            ..Default::default()
+        };
+        let response = super::spent(app_state.clone(), Json(req)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(app_state.state.read().unwrap().snapshot(), before);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_recurring_changes_nothing() {
+        let app_state = overdue_ledger();
+        let before = app_state.state.read().unwrap().snapshot();
+        let req = RecurringRequest {
+            amount: 15.0,
+            category: Some(Category::new("Nonsense")),
+            schedule: Schedule::Monthly { day: 1 },
+            start: None,
+        };
+        let response = add_recurring(app_state.clone(), Json(req.clone())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let req = RecurringRequest {
+            category: None,
+            ..req
+        };
+        let response = update_recurring(app_state.clone(), Path(7), Json(req)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(app_state.state.read().unwrap().snapshot(), before);
+    }
+
+    #[test]
+    fn test_reset_periods_dont_overlap() {
+        let config = PeriodConfig::new(1, false);
+        let mut state = StateTotal::new(date(1, 1));
+        state.push(spent(1, 5, 10_000));
+        state.reset(date(1, 10), &config);
+        assert_eq!(state.period_start, date(1, 11));
+        state.push(spent(1, 20, 5_000));
+        state.catch_up(date(2, 3), &config);
+
+        let bounds: Vec<_> = state.periods.iter().map(|p| (p.start, p.end)).collect();
+        assert_eq!(
+            bounds,
+            vec![(date(1, 1), date(1, 10)), (date(1, 11), date(1, 31))]
+        );
+        assert_eq!(state.period_start, date(2, 1));
+    }
+
+    #[test]
+    fn test_catch_up_closes_elapsed_periods() {
+        let config = PeriodConfig::new(1, true);
+        let mut state = StateTotal::new(date(1, 1));
+        for entry in [
+            spent(1, 5, 10_000),
+            spent(1, 31, 5_000),
+            spent(2, 2, 60_000),
+        ] {
+            state.push(entry);
+        }
+        state.alerts_sent.push(Alert {
+            category: None,
+            threshold: 50,
+        });
+        assert!(!state.catch_up(date(1, 31), &config));
+
+        assert!(state.catch_up(date(3, 10), &config));
+        assert_eq!(state.period_start, date(3, 1));
+        assert!(state.transactions.is_empty());
+        assert!(state.alerts_sent.is_empty());
+        let bounds: Vec<_> = state.periods.iter().map(|p| (p.start, p.end)).collect();
+        assert_eq!(
+            bounds,
+            vec![(date(1, 1), date(1, 31)), (date(2, 1), date(2, 28))]
+        );
+        assert_eq!(state.periods[0].entries, Some(vec![1, 2]));
+        assert_eq!(state.periods[0].total, "$150.00");
+        // January's $350 left over carries into February, and what February left into March
+        assert_eq!(state.periods[1].budget, "$850.00");
+        assert_eq!(state.carried_over, usd(25_000));
+        assert_eq!(state.history.len(), 3);
+    }
+
+    #[test]
+    fn test_catch_up_rollover() {
+        let mut state = StateTotal::new(date(1, 15));
+        state.push(spent(1, 20, 20_000));
+        let mut kept = state.clone();
+
+        state.catch_up(date(2, 15), &PeriodConfig::new(15, true));
+        assert_eq!(state.periods[0].end, date(2, 14));
+        assert_eq!(state.carried_over, usd(30_000));
+        assert_eq!(state.available(), usd(80_000));
+
+        kept.catch_up(date(2, 15), &PeriodConfig::new(15, false));
+        assert_eq!(kept.carried_over, usd(0));
+    }
+
+    #[test]
+    fn test_catch_up_posts_recurring_into_their_period() {
+        let mut state = StateTotal::new(date(1, 1));
+        state.recurring.push(Recurring {
+            id: 1,
+            amount: 1_599,
+            category: Category::Entertainment,
+            schedule: Schedule::Monthly { day: 15 },
+            next_due: date(1, 15),
+        });
+        assert!(state.catch_up(date(2, 20), &PeriodConfig::default()));
+        assert_eq!(state.recurring[0].next_due, date(3, 15));
+        assert_eq!(state.periods.len(), 1);
+        assert_eq!(state.periods[0].entries.as_ref().map(Vec::len), Some(1));
+        assert_eq!(state.transactions.len(), 1);
+        assert_eq!(state.transactions[0].time.date_naive(), date(2, 15));
+    }
+
+    #[test]
+    fn test_import_files_entries_by_date() {
+        let config = PeriodConfig::default();
+        let mut state = StateTotal::new(date(1, 1));
+        state.push(spent(1, 10, 1_000));
+        state.catch_up(date(2, 3), &config);
+        state.push(spent(2, 2, 2_000));
+
+        let records = [
+            spent(1, 10, 1_000),
+            spent(1, 20, 3_000),
+            spent(2, 2, 2_000),
+            spent(2, 5, 4_000),
+        ];
+        assert_eq!(state.import(records.clone()), 2);
+        assert_eq!(state.history.len(), 2);
+        assert_eq!(state.transactions.len(), 2);
+        assert_eq!(state.total(), usd(6_000));
+        assert_eq!(state.import(records), 0);
+
+        let everything = state.exported(&ExportQuery::default());
+        assert_eq!(everything.len(), 4);
+        assert!(everything
+            .windows(2)
+            .all(|pair| pair[0].time <= pair[1].time));
+        let january = ExportQuery {
+            from: Some(date(1, 15)),
+            to: Some(date(1, 31)),
+        };
+        assert_eq!(state.exported(&january).len(), 1);
+    }
+
+    #[test]
+    fn test_import_statement_keeps_identical_charges() {
+        let coffee = "D03/04/2025\nT-4.50\nPKALDI'S\n^\n";
+        let statement = format!("!Type:Bank\n{coffee}{coffee}");
+        let entries = |state: &StateTotal| -> Vec<Entry> {
+            statement::parse_qif(&statement)
+                .unwrap()
+                .iter()
+                .filter_map(|line| line.entry(&state.rules))
+                .collect()
+        };
+        let mut state = StateTotal::new(date(3, 1));
+        assert_eq!(state.import(entries(&state)), 2);
+        assert_eq!(state.import(entries(&state)), 0);
+        assert_eq!(state.total(), usd(900));
+    }
+
+    #[test]
+    fn test_find_period_entries() {
+        let mut state = StateTotal::new(date(1, 1));
+        for entry in [
+            spent(1, 5, 100),
+            spent(1, 31, 200),
+            spent(2, 10, 300),
+            spent(3, 1, 400),
+        ] {
+            state.push(entry);
+        }
+        state.catch_up(date(3, 2), &PeriodConfig::default());
+        assert_eq!(state.periods[1].entries, Some(vec![3]));
+
+        // as a file from before periods kept their entries has it
+        let mut snapshot = state.snapshot();
+        for period in &mut snapshot.periods {
+            period.entries = None;
+        }
+        let loaded = StateTotal::from_snapshot(snapshot, date(3, 1));
+        assert_eq!(loaded.periods[0].entries, Some(vec![1, 2]));
+        assert_eq!(loaded.periods[1].entries, Some(vec![3]));
+
+        let detail = PeriodDetail::new(&loaded.periods[0], &loaded.history);
+        assert_eq!(detail.transactions.len(), 2);
+        assert_eq!(detail.transactions[1].amount, "$2.00");
+    }
+
+    #[test]
+    fn test_check_alerts_leaves_out_sent_and_sending() {
+        let mut state = StateTotal::new(date(1, 1));
+        state.category_budgets.insert(Category::Dining, usd(10_000));
+        state.push(spent(1, 5, 30_000));
+        let overall = |threshold| Alert {
+            category: None,
+            threshold,
+        };
+        let dining = |threshold| Alert {
+            category: Some(Category::Dining),
+            threshold,
+        };
+        state.alerts_sent.push(overall(50));
+
+        let outgoing = state.check_alerts(&[50, 80, 100], &[dining(50)]);
+        let alerts: Vec<_> = outgoing.iter().map(|o| o.alerts.clone()).collect();
+        assert_eq!(alerts, vec![vec![dining(80), dining(100)]]);
+        assert!(outgoing[0].message.contains("100% of the"));
+        // nothing counts as sent until it has been delivered
+        assert_eq!(state.alerts_sent, vec![overall(50)]);
+    }
+
+    #[test]
+    fn test_household_shares_categories_and_rates() {
+        let household =
+            Household::new(None, PeriodConfig::new(1, false), AlertConfig::default()).unwrap();
+        let trip = household.open("trip").unwrap();
+        if let Ok(mut state) = trip.state.write() {
+            state.categories.ensure(&Category::new("Pets"));
+            state
+                .rates
+                .insert("EUR".to_string(), rust_decimal::Decimal::new(11, 1));
+            state.push(Entry {
+                category: Category::new("Pets"),
+                ..spent(1, 5, 1_000)
+            });
+        }
+        household.share(&trip, None).unwrap();
+        let shared = household.default.state.read().unwrap();
+        assert!(shared.categories.find("pets").is_some());
+        assert_eq!(shared.rates, trip.state.read().unwrap().rates);
+        drop(shared);
+
+        let animals = Category::new("Animals");
+        household
+            .share(
+                &household.default,
+                Some((Category::new("Pets"), animals.clone())),
+            )
+            .unwrap();
+        assert_eq!(trip.state.read().unwrap().transactions[0].category, animals);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::ledger::Entry;
+use crate::{CategoryBudget, Transaction};
+use chrono::{Datelike, Months, NaiveDate};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// How budget periods are laid out. Periods are one month long and begin on `start_day`.
+#[derive(Clone, Copy, Debug)]
+pub struct PeriodConfig {
+    pub start_day: u32,
+    /// Carry whatever was left of the budget into the next period.
+    pub rollover: bool,
+}
+
+impl Default for PeriodConfig {
+    fn default() -> Self {
+        Self {
+            start_day: 1,
+            rollover: false,
+        }
+    }
+}
+
+impl PeriodConfig {
+    /// Start day is clamped to 1..=28 so every month has one.
+    pub fn new(start_day: u32, rollover: bool) -> Self {
+        Self {
+            start_day: start_day.clamp(1, 28),
+            rollover,
+        }
+    }
+
+    /// First day of the period containing `date`.
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        let start = date.with_day(self.start_day).unwrap_or(date);
+        if date.day() >= self.start_day {
+            start
+        } else {
+            start - Months::new(1)
+        }
+    }
+
+    /// First day of the period following the one containing `date`.
+    pub fn next_start(&self, date: NaiveDate) -> NaiveDate {
+        self.start_of(date) + Months::new(1)
+    }
+}
+
+/// A closed budget period. What was spent in it moves to the ledger's history, and the
+/// period keeps which entries those were.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Period {
+    pub id: usize,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub budget: String,
+    pub total: String,
+    pub categories: Vec<CategoryBudget>,
+    /// Ids of the entries archived when the period closed. `None` for periods closed before
+    /// these were kept, until the ledger works them out on loading.
+    #[serde(default)]
+    pub entries: Option<Vec<u64>>,
+}
+
+/// A closed period along with everything spent in it, as `/periods/{id}` lists it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PeriodDetail {
+    pub id: usize,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub budget: String,
+    pub total: String,
+    pub categories: Vec<CategoryBudget>,
+    pub transactions: Vec<Transaction>,
+}
+
+impl PeriodDetail {
+    /// Looks up the period's entries in the ledger's `history`.
+    pub fn new<'e>(period: &Period, history: impl IntoIterator<Item = &'e Entry>) -> Self {
+        let ids = period.entries.as_deref().unwrap_or_default();
+        Self {
+            id: period.id,
+            start: period.start,
+            end: period.end,
+            budget: period.budget.clone(),
+            total: period.total.clone(),
+            categories: period.categories.clone(),
+            transactions: history
+                .into_iter()
+                .filter(|entry| ids.contains(&entry.id))
+                .map(Entry::transaction)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PeriodSummary {
+    pub id: usize,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub budget: String,
+    pub total: String,
+}
+
+impl From<&Period> for PeriodSummary {
+    fn from(period: &Period) -> Self {
+        Self {
+            id: period.id,
+            start: period.start,
+            end: period.end,
+            budget: period.budget.clone(),
+            total: period.total.clone(),
+        }
+    }
+}
+
+impl fmt::Display for PeriodSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} to {}: {} of {}",
+            self.id, self.start, self.end, self.total, self.budget
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let monthly = PeriodConfig::default();
+        assert_eq!(monthly.start_of(date(2025, 3, 17)), date(2025, 3, 1));
+        assert_eq!(monthly.next_start(date(2025, 12, 31)), date(2026, 1, 1));
+
+        let payday = PeriodConfig::new(15, false);
+        assert_eq!(payday.start_of(date(2025, 3, 15)), date(2025, 3, 15));
+        assert_eq!(payday.start_of(date(2025, 3, 14)), date(2025, 2, 15));
+        assert_eq!(payday.start_of(date(2025, 1, 2)), date(2024, 12, 15));
+        assert_eq!(payday.next_start(date(2025, 3, 14)), date(2025, 3, 15));
+
+        assert_eq!(PeriodConfig::new(31, false).start_day, 28);
+    }
+}
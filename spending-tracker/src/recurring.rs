@@ -0,0 +1,138 @@
+use crate::ledger::usd;
+use crate::Category;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Schedule {
+    /// On the given day of every month, or the month's last day if it is shorter.
+    Monthly { day: u32 },
+    /// Every `every` weeks, counting from the start date.
+    Weekly { every: u32 },
+}
+
+impl Schedule {
+    /// The first due date on or after `from`.
+    pub fn first_due(&self, from: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Monthly { day } => {
+                let due = day_in_month(from, *day);
+                if due >= from {
+                    due
+                } else {
+                    day_in_month(from + Months::new(1), *day)
+                }
+            }
+            Self::Weekly { .. } => from,
+        }
+    }
+
+    /// The due date following `due`.
+    pub fn next_due(&self, due: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Monthly { day } => {
+                day_in_month(due.with_day(1).unwrap_or(due) + Months::new(1), *day)
+            }
+            Self::Weekly { every } => due + Days::new(7 * u64::from((*every).max(1))),
+        }
+    }
+}
+
+/// `day` of the month `date` falls in, clamped to the month's length.
+fn day_in_month(date: NaiveDate, day: u32) -> NaiveDate {
+    (1..=day.clamp(1, 31))
+        .rev()
+        .find_map(|day| date.with_day(day))
+        .unwrap_or(date)
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Monthly { day } => write!(f, "monthly on day {day}"),
+            Self::Weekly { every: 0 | 1 } => write!(f, "weekly"),
+            Self::Weekly { every } => write!(f, "every {every} weeks"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RecurringRequest {
+    pub amount: f32,
+    pub category: Option<Category>,
+    pub schedule: Schedule,
+    /// First day the expense can fall due. Defaults to today.
+    #[serde(default)]
+    pub start: Option<NaiveDate>,
+}
+
+/// An expense that is posted to the ledger automatically whenever it falls due.
+/// `amount` is in minor units (cents).
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Recurring {
+    pub id: u64,
+    pub amount: i64,
+    pub category: Category,
+    pub schedule: Schedule,
+    pub next_due: NaiveDate,
+}
+
+impl fmt::Display for Recurring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} {} {}, next on {}",
+            self.id,
+            usd(self.amount),
+            self.category,
+            self.schedule,
+            self.next_due
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RecurringResponse(pub Vec<Recurring>);
+
+impl fmt::Display for RecurringResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "no recurring expenses");
+        }
+        writeln!(f, "Recurring expenses")?;
+        for recurring in &self.0 {
+            writeln!(f, "• {}", recurring)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_monthly_schedule() {
+        let rent = Schedule::Monthly { day: 1 };
+        assert_eq!(rent.first_due(date(2025, 3, 1)), date(2025, 3, 1));
+        assert_eq!(rent.first_due(date(2025, 3, 2)), date(2025, 4, 1));
+        assert_eq!(rent.next_due(date(2025, 12, 1)), date(2026, 1, 1));
+
+        let end_of_month = Schedule::Monthly { day: 31 };
+        assert_eq!(end_of_month.first_due(date(2025, 2, 10)), date(2025, 2, 28));
+        assert_eq!(end_of_month.next_due(date(2025, 2, 28)), date(2025, 3, 31));
+    }
+
+    #[test]
+    fn test_weekly_schedule() {
+        let biweekly = Schedule::Weekly { every: 2 };
+        assert_eq!(biweekly.first_due(date(2025, 3, 5)), date(2025, 3, 5));
+        assert_eq!(biweekly.next_due(date(2025, 3, 5)), date(2025, 3, 19));
+        assert_eq!(biweekly.to_string(), "every 2 weeks");
+    }
+}
@@ -0,0 +1,371 @@
+use crate::goals::GoalProgress;
+use crate::ledger::{self, usd, Entry};
+use crate::period::PeriodConfig;
+use crate::{Category, Kind, Transaction};
+use chrono::{Datelike, Days, NaiveDate};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// How many of the largest expenses a report lists.
+const TOP_TRANSACTIONS: usize = 3;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    /// Monday through today, compared against last Monday through Sunday.
+    Week,
+    /// The current budget period, compared against the one before it.
+    #[default]
+    Month,
+}
+
+impl ReportPeriod {
+    /// First day of the period containing `date`.
+    pub fn start_of(&self, date: NaiveDate, config: &PeriodConfig) -> NaiveDate {
+        match self {
+            Self::Week => date - Days::new(u64::from(date.weekday().num_days_from_monday())),
+            Self::Month => config.start_of(date),
+        }
+    }
+
+    /// First and last day of the period before the one starting on `start`.
+    pub fn previous(&self, start: NaiveDate, config: &PeriodConfig) -> (NaiveDate, NaiveDate) {
+        let end = start.pred_opt().unwrap_or(start);
+        (self.start_of(end, config), end)
+    }
+}
+
+impl fmt::Display for ReportPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Week => write!(f, "week"),
+            Self::Month => write!(f, "month"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct ReportQuery {
+    #[serde(default)]
+    pub period: ReportPeriod,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CategoryTotal {
+    pub category: Category,
+    pub total: String,
+    pub previous: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Report {
+    pub period: ReportPeriod,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub total: String,
+    pub daily_average: String,
+    /// Daily spending as a percentage of what the budget allows per day. Over 100 means the
+    /// budget will run out before the period does.
+    pub burn_rate: i64,
+    pub categories: Vec<CategoryTotal>,
+    pub top: Vec<Transaction>,
+    pub previous_total: String,
+    /// Percentage change against the previous period, if anything was spent in it.
+    pub change: Option<i64>,
+    /// How each savings goal is coming along.
+    #[serde(default)]
+    pub goals: Vec<GoalProgress>,
+}
+
+/// Builds a report for the period containing `today` from every entry the tracker knows of,
+/// archived or not. `available` is the budget for the current budget period.
+pub fn build<'e>(
+    period: ReportPeriod,
+    entries: impl IntoIterator<Item = &'e Entry>,
+    today: NaiveDate,
+    config: &PeriodConfig,
+    available: i64,
+) -> Report {
+    let start = period.start_of(today, config);
+    let (previous_start, previous_end) = period.previous(start, config);
+    let (mut current, mut previous) = (Vec::new(), Vec::new());
+    for entry in entries {
+        let date = entry.time.date_naive();
+        if (start..=today).contains(&date) {
+            current.push(entry);
+        } else if (previous_start..=previous_end).contains(&date) {
+            previous.push(entry);
+        }
+    }
+
+    let total = ledger::to_minor(&ledger::total(current.iter().copied(), None));
+    let previous_total = ledger::to_minor(&ledger::total(previous.iter().copied(), None));
+    let days = (today - start).num_days() + 1;
+    let budget_days = (config.next_start(today) - config.start_of(today)).num_days();
+    let daily_average = total / days;
+
+    let mut categories: Vec<Category> = current
+        .iter()
+        .chain(&previous)
+        .filter(|entry| entry.kind != Kind::Income)
+        .flat_map(|entry| entry.categories().cloned())
+        .collect();
+    categories.sort();
+    categories.dedup();
+
+    let mut top: Vec<&Entry> = current
+        .iter()
+        .copied()
+        .filter(|entry| entry.kind == Kind::Expense)
+        .collect();
+    top.sort_by_key(|entry| std::cmp::Reverse(entry.amount));
+
+    Report {
+        period,
+        start,
+        end: today,
+        total: usd(total).to_string(),
+        daily_average: usd(daily_average).to_string(),
+        burn_rate: percent(daily_average * budget_days, available).unwrap_or_default(),
+        categories: categories
+            .into_iter()
+            .map(|category| CategoryTotal {
+                total: ledger::total(current.iter().copied(), Some(&category)).to_string(),
+                previous: ledger::total(previous.iter().copied(), Some(&category)).to_string(),
+                category,
+            })
+            .collect(),
+        top: top
+            .into_iter()
+            .take(TOP_TRANSACTIONS)
+            .map(Entry::transaction)
+            .collect(),
+        previous_total: usd(previous_total).to_string(),
+        change: percent(total - previous_total, previous_total),
+        goals: Vec::new(),
+    }
+}
+
+/// How many periods a trend covers when the query doesn't say.
+pub const TREND_PERIODS: usize = 6;
+/// The most periods a trend will go back, however many are asked for.
+const MAX_TREND_PERIODS: usize = 52;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct TrendQuery {
+    #[serde(default)]
+    pub period: ReportPeriod,
+    /// How many periods to cover, the current one included.
+    #[serde(default)]
+    pub count: Option<usize>,
+}
+
+/// Spending in each category over the last few periods, oldest first.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Trend {
+    pub period: ReportPeriod,
+    /// First day of each period covered.
+    pub starts: Vec<NaiveDate>,
+    pub categories: Vec<CategoryTrend>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CategoryTrend {
+    pub category: Category,
+    /// What was spent in each period, lined up with `Trend::starts`.
+    pub totals: Vec<String>,
+}
+
+/// Builds a trend of the `count` periods up to and including the one containing `today`.
+pub fn trend<'e>(
+    period: ReportPeriod,
+    entries: impl IntoIterator<Item = &'e Entry>,
+    today: NaiveDate,
+    config: &PeriodConfig,
+    count: usize,
+) -> Trend {
+    let mut starts = vec![period.start_of(today, config)];
+    while starts.len() < count.clamp(1, MAX_TREND_PERIODS) {
+        let (start, _) = period.previous(starts[starts.len() - 1], config);
+        starts.push(start);
+    }
+    starts.reverse();
+
+    let mut buckets: Vec<Vec<&Entry>> = vec![Vec::new(); starts.len()];
+    for entry in entries {
+        let date = entry.time.date_naive();
+        if date < starts[0] || date > today {
+            continue;
+        }
+        let bucket = starts.partition_point(|start| *start <= date) - 1;
+        buckets[bucket].push(entry);
+    }
+
+    let mut categories: Vec<Category> = buckets
+        .iter()
+        .flatten()
+        .filter(|entry| entry.kind != Kind::Income)
+        .flat_map(|entry| entry.categories().cloned())
+        .collect();
+    categories.sort();
+    categories.dedup();
+
+    Trend {
+        period,
+        starts,
+        categories: categories
+            .into_iter()
+            .map(|category| CategoryTrend {
+                totals: buckets
+                    .iter()
+                    .map(|bucket| {
+                        ledger::total(bucket.iter().copied(), Some(&category)).to_string()
+                    })
+                    .collect(),
+                category,
+            })
+            .collect(),
+    }
+}
+
+fn percent(part: i64, whole: i64) -> Option<i64> {
+    (whole != 0).then(|| part * 100 / whole)
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "This {} ({} to {})",
+            self.period,
+            self.start.format("%b %-d"),
+            self.end.format("%b %-d")
+        )?;
+        writeln!(f, "spent: {} ({}/day)", self.total, self.daily_average)?;
+        writeln!(f, "burn rate: {}% of budget pace", self.burn_rate)?;
+        for category in &self.categories {
+            writeln!(
+                f,
+                "• {}: {} (last {} {})",
+                category.category, category.total, self.period, category.previous
+            )?;
+        }
+        if !self.top.is_empty() {
+            writeln!(f, "top:")?;
+            for transaction in &self.top {
+                writeln!(
+                    f,
+                    "• {} {} {}",
+                    transaction.amount,
+                    transaction.category,
+                    transaction.time.get(..10).unwrap_or(&transaction.time)
+                )?;
+            }
+        }
+        write!(f, "last {}: {}", self.period, self.previous_total)?;
+        if let Some(change) = self.change {
+            write!(f, " ({change:+}%)")?;
+        }
+        if !self.goals.is_empty() {
+            write!(f, "\nsavings:")?;
+            for goal in &self.goals {
+                write!(f, "\n• {goal}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn entry(y: i32, m: u32, d: u32, amount: i64, category: Category) -> Entry {
+        Entry {
+            amount,
+            category,
+            time: Local.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_month_report() {
+        let entries = vec![
+            entry(2025, 2, 10, 10000, Category::Grocery),
+            entry(2025, 3, 2, 3000, Category::Dining),
+            entry(2025, 3, 5, 9000, Category::Grocery),
+            entry(2025, 3, 9, 3000, Category::Dining),
+        ];
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let report = build(
+            ReportPeriod::Month,
+            &entries,
+            today,
+            &PeriodConfig::default(),
+            31000,
+        );
+
+        assert_eq!(report.start, NaiveDate::from_ymd_opt(2025, 3, 1).unwrap());
+        assert_eq!(report.total, "$150.00");
+        assert_eq!(report.daily_average, "$15.00");
+        assert_eq!(report.burn_rate, 150);
+        assert_eq!(report.previous_total, "$100.00");
+        assert_eq!(report.change, Some(50));
+        assert_eq!(report.categories[0].category, Category::Dining);
+        assert_eq!(report.categories[1].previous, "$100.00");
+        assert_eq!(report.top[0].amount, "$90.00");
+    }
+
+    #[test]
+    fn test_week_bounds() {
+        let config = PeriodConfig::default();
+        // a Wednesday
+        let today = NaiveDate::from_ymd_opt(2025, 3, 12).unwrap();
+        let start = ReportPeriod::Week.start_of(today, &config);
+        assert_eq!(start, NaiveDate::from_ymd_opt(2025, 3, 10).unwrap());
+        assert_eq!(
+            ReportPeriod::Week.previous(start, &config),
+            (
+                NaiveDate::from_ymd_opt(2025, 3, 3).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 9).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_month_trend() {
+        let entries = vec![
+            entry(2024, 12, 20, 5000, Category::Grocery),
+            entry(2025, 1, 15, 4000, Category::Grocery),
+            entry(2025, 3, 2, 3000, Category::Dining),
+            entry(2025, 3, 5, 9000, Category::Grocery),
+        ];
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let trend = trend(
+            ReportPeriod::Month,
+            &entries,
+            today,
+            &PeriodConfig::default(),
+            3,
+        );
+
+        assert_eq!(
+            trend.starts,
+            vec![
+                NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 2, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+            ]
+        );
+        // December is before the first period, so only Dining and Grocery show up
+        assert_eq!(trend.categories.len(), 2);
+        assert_eq!(trend.categories[0].category, Category::Dining);
+        assert_eq!(trend.categories[0].totals, vec!["$0.00", "$0.00", "$30.00"]);
+        assert_eq!(
+            trend.categories[1].totals,
+            vec!["$40.00", "$0.00", "$90.00"]
+        );
+    }
+}
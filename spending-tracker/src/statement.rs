@@ -0,0 +1,301 @@
+use crate::ledger::{to_minor, Entry};
+use crate::Category;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A line from a bank or card statement. Negative amounts are money leaving the account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatementLine {
+    pub time: DateTime<Local>,
+    pub amount: Decimal,
+    pub payee: String,
+    /// Identifies the line across imports of overlapping statements. The bank's FITID when
+    /// it gives one, otherwise made up from the line's date, amount and payee and how many
+    /// identical lines came before it in the file.
+    pub id: String,
+}
+
+/// Maps statement payees containing `pattern` (case insensitive) to a category.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Rule {
+    pub pattern: String,
+    pub category: Category,
+}
+
+/// Picks the category of the first rule matching `payee`, or `Other` if none do.
+pub fn categorize(rules: &[Rule], payee: &str) -> Category {
+    let payee = payee.to_lowercase();
+    rules
+        .iter()
+        .find(|rule| payee.contains(&rule.pattern.to_lowercase()))
+        .map_or(Category::Other, |rule| rule.category.clone())
+}
+
+impl StatementLine {
+    /// The spending entry for this line, or `None` for deposits and other credits.
+    pub fn entry(&self, rules: &[Rule]) -> Option<Entry> {
+        if !self.amount.is_sign_negative() || self.amount.is_zero() {
+            return None;
+        }
+        Some(Entry {
+            id: 0,
+            amount: to_minor(&Money::from_decimal(-self.amount, iso::USD)),
+            category: categorize(rules, &self.payee),
+            time: self.time,
+            merchant: (!self.payee.is_empty()).then(|| self.payee.clone()),
+            import_id: Some(self.id.clone()),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid statement: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the `<STMTTRN>` blocks of an OFX or QFX file. Both the SGML (1.x) and XML (2.x)
+/// flavours are accepted since only the opening tags are looked at.
+pub fn parse_ofx(data: &str) -> Result<Vec<StatementLine>, ParseError> {
+    let upper = data.to_ascii_uppercase();
+    if !upper.contains("<OFX>") {
+        return Err(ParseError("missing <OFX> tag".to_string()));
+    }
+    let lines = upper
+        .match_indices("<STMTTRN>")
+        .map(|(start, _)| {
+            let end = upper[start..]
+                .find("</STMTTRN>")
+                .map_or(upper.len(), |end| start + end);
+            let block = &data[start..end];
+            let date = ofx_field(block, "DTPOSTED")
+                .ok_or_else(|| ParseError("transaction without DTPOSTED".to_string()))?;
+            let amount = ofx_field(block, "TRNAMT")
+                .ok_or_else(|| ParseError("transaction without TRNAMT".to_string()))?;
+            Ok(StatementLine {
+                time: parse_ofx_date(&date)?,
+                amount: parse_amount(&amount)?,
+                payee: ofx_field(block, "NAME")
+                    .or_else(|| ofx_field(block, "MEMO"))
+                    .unwrap_or_default(),
+                id: ofx_field(block, "FITID").unwrap_or_default(),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(with_ids(lines))
+}
+
+/// Gives lines the statement didn't identify an id of their own. Identical lines are told
+/// apart by their order, so two real charges for the same amount on the same day both count
+/// while importing the file again still matches them up.
+fn with_ids(mut lines: Vec<StatementLine>) -> Vec<StatementLine> {
+    let mut seen: Vec<String> = Vec::new();
+    for line in lines.iter_mut().filter(|line| line.id.is_empty()) {
+        let key = format!(
+            "{}|{}|{}",
+            line.time.date_naive(),
+            line.amount.normalize(),
+            line.payee
+        );
+        let repeat = seen.iter().filter(|earlier| **earlier == key).count();
+        line.id = format!("{key}|{repeat}");
+        seen.push(key);
+    }
+    lines
+}
+
+/// Text following `<tag>` up to the next tag or line break.
+fn ofx_field(block: &str, tag: &str) -> Option<String> {
+    let open = format!("<{tag}>");
+    let start = block.to_ascii_uppercase().find(&open)? + open.len();
+    let value = block[start..]
+        .split(['<', '\n', '\r'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    Some(value.to_string())
+}
+
+/// OFX dates look like `YYYYMMDD[HHMMSS[.XXX]][[-5:EST]]`. The zone suffix is ignored.
+fn parse_ofx_date(s: &str) -> Result<DateTime<Local>, ParseError> {
+    let digits: String = s.chars().take_while(char::is_ascii_digit).collect();
+    let date = digits
+        .get(..8)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+        .ok_or_else(|| ParseError(format!("bad date {s}")))?;
+    let time = digits
+        .get(8..14)
+        .and_then(|t| NaiveTime::parse_from_str(t, "%H%M%S").ok())
+        .unwrap_or_default();
+    local(date.and_time(time)).ok_or_else(|| ParseError(format!("bad date {s}")))
+}
+
+/// Parses a QIF file's `!Type:Bank` or `!Type:CCard` records, which are separated by `^`.
+pub fn parse_qif(data: &str) -> Result<Vec<StatementLine>, ParseError> {
+    if !data.trim_start().starts_with("!Type") {
+        return Err(ParseError("missing !Type header".to_string()));
+    }
+    let mut lines = Vec::new();
+    let (mut date, mut amount, mut payee) = (None, None, String::new());
+    for line in data.lines().map(str::trim) {
+        let Some(code) = line.chars().next() else {
+            continue;
+        };
+        let value = &line[code.len_utf8()..];
+        match code {
+            'D' => date = Some(parse_qif_date(value)?),
+            'T' | 'U' => amount = Some(parse_amount(value)?),
+            'P' => payee = value.to_string(),
+            'M' if payee.is_empty() => payee = value.to_string(),
+            '^' => {
+                let (Some(time), Some(amount)) = (date.take(), amount.take()) else {
+                    return Err(ParseError("record without date or amount".to_string()));
+                };
+                lines.push(StatementLine {
+                    time,
+                    amount,
+                    payee: std::mem::take(&mut payee),
+                    id: String::new(),
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(with_ids(lines))
+}
+
+/// QIF dates are US style, with either a slash or an apostrophe before two-digit years.
+fn parse_qif_date(s: &str) -> Result<DateTime<Local>, ParseError> {
+    let s = s.trim().replace('\'', "/").replace(' ', "0");
+    let format = match s.rsplit('/').next() {
+        Some(year) if year.len() == 2 => "%m/%d/%y",
+        _ if s.contains('-') => "%Y-%m-%d",
+        _ => "%m/%d/%Y",
+    };
+    NaiveDate::parse_from_str(&s, format)
+        .ok()
+        .and_then(|date| local(date.and_time(NaiveTime::default())))
+        .ok_or_else(|| ParseError(format!("bad date {s}")))
+}
+
+fn parse_amount(s: &str) -> Result<Decimal, ParseError> {
+    Decimal::from_str(&s.trim().replace(',', "")).map_err(|e| ParseError(e.to_string()))
+}
+
+fn local(t: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&t).earliest()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OFX: &str = "OFXHEADER:100
+DATA:OFXSGML
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<BANKTRANLIST>
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20250103120000[-6:CST]
+<TRNAMT>-24.78
+<FITID>1001
+<NAME>SCHNUCKS #123
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20250104
+<TRNAMT>1,200.00
+<FITID>1002
+<NAME>PAYROLL
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>";
+
+    const QIF: &str = "!Type:CCard
+D01/05/2025
+T-15.99
+PNETFLIX.COM
+^
+D1/6'25
+T-1,042.10
+PDELTA AIR
+MBooking
+^
+";
+
+    fn rules() -> Vec<Rule> {
+        vec![
+            Rule {
+                pattern: "schnucks".to_string(),
+                category: Category::Grocery,
+            },
+            Rule {
+                pattern: "Delta".to_string(),
+                category: Category::Travel,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_parse_ofx() {
+        let lines = parse_ofx(OFX).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].payee, "SCHNUCKS #123");
+        assert_eq!(lines[0].amount, Decimal::new(-2478, 2));
+        assert_eq!(lines[1].amount, Decimal::new(120000, 2));
+
+        assert_eq!(lines[0].id, "1001");
+
+        let entries: Vec<Entry> = lines.iter().filter_map(|l| l.entry(&rules())).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].import_id.as_deref(), Some("1001"));
+        assert_eq!(entries[0].amount, 2478);
+        assert_eq!(entries[0].category, Category::Grocery);
+        assert_eq!(entries[0].merchant.as_deref(), Some("SCHNUCKS #123"));
+
+        assert!(parse_ofx("not a statement").is_err());
+    }
+
+    #[test]
+    fn test_parse_qif() {
+        let lines = parse_qif(QIF).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1].time.date_naive(),
+            NaiveDate::from_ymd_opt(2025, 1, 6).unwrap()
+        );
+
+        assert_eq!(lines[0].id, "2025-01-05|-15.99|NETFLIX.COM|0");
+
+        let entries: Vec<Entry> = lines.iter().filter_map(|l| l.entry(&rules())).collect();
+        assert_eq!(entries[0].category, Category::Other);
+        assert_eq!(entries[1].amount, 104210);
+        assert_eq!(entries[1].category, Category::Travel);
+    }
+
+    #[test]
+    fn test_identical_lines_get_their_own_ids() {
+        let coffee = "D03/04/2025\nT-4.50\nPKALDI'S\n^\n";
+        let lines = parse_qif(&format!("!Type:Bank\n{coffee}{coffee}")).unwrap();
+        assert_eq!(lines[0].id, "2025-03-04|-4.5|KALDI'S|0");
+        assert_eq!(lines[1].id, "2025-03-04|-4.5|KALDI'S|1");
+        // the same file imported again gives the same ids
+        assert_eq!(
+            parse_qif(&format!("!Type:Bank\n{coffee}{coffee}")).unwrap(),
+            lines
+        );
+    }
+}
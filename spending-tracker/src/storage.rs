@@ -0,0 +1,252 @@
+use crate::alert::Alert;
+use crate::categories::Categories;
+use crate::currency::Rates;
+use crate::goals::Goal;
+use crate::ledger::{to_minor, Entry};
+use crate::period::Period;
+use crate::recurring::Recurring;
+use crate::statement::Rule;
+use crate::{Category, Transaction};
+use chrono::{DateTime, Local, NaiveDate};
+use rust_decimal::Decimal;
+use rusty_money::{iso, Money};
+use serde::de::{Deserializer, Error};
+use serde::Deserialize as _;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Everything the tracker needs to rebuild its state after a restart.
+/// Money values are kept in minor units (cents) so they survive a round trip exactly.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Snapshot {
+    pub budget: i64,
+    #[serde(default)]
+    pub category_budgets: BTreeMap<Category, i64>,
+    #[serde(deserialize_with = "entries")]
+    pub transactions: Vec<Entry>,
+    #[serde(default)]
+    pub next_id: u64,
+    #[serde(default)]
+    pub period_start: Option<NaiveDate>,
+    #[serde(default)]
+    pub carried_over: i64,
+    #[serde(default)]
+    pub periods: Vec<Period>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub recurring: Vec<Recurring>,
+    #[serde(default)]
+    pub next_recurring_id: u64,
+    /// Entries from closed periods, kept so reports can look back at them. Each period lists
+    /// which of these it archived.
+    #[serde(default)]
+    pub history: Vec<Entry>,
+    /// Budget alerts already sent this period.
+    #[serde(default)]
+    pub alerts_sent: Vec<Alert>,
+    #[serde(default)]
+    pub rates: Rates,
+    #[serde(default)]
+    pub categories: Categories,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    /// Names of the other ledgers. Only kept by the default ledger.
+    #[serde(default)]
+    pub ledgers: Vec<String>,
+    /// Chats that log to one of those ledgers instead of this one.
+    #[serde(default)]
+    pub chat_ledgers: BTreeMap<i64, String>,
+}
+
+/// Files written before transactions had ids kept them the way `GET /spent` lists them, with
+/// formatted amounts and times. Those are read back as entries with no id yet.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredEntry {
+    Entry(Entry),
+    Listed(Transaction),
+}
+
+fn entries<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Entry>, D::Error> {
+    Vec::<StoredEntry>::deserialize(deserializer)?
+        .into_iter()
+        .map(|stored| match stored {
+            StoredEntry::Entry(entry) => Ok(entry),
+            StoredEntry::Listed(transaction) => listed_entry(&transaction)
+                .ok_or_else(|| D::Error::custom(format!("unreadable transaction {transaction:?}"))),
+        })
+        .collect()
+}
+
+fn listed_entry(transaction: &Transaction) -> Option<Entry> {
+    let amount: Decimal = transaction
+        .amount
+        .trim_start_matches('$')
+        .replace(',', "")
+        .parse()
+        .ok()?;
+    let time = DateTime::parse_from_str(&transaction.time, "%Y-%m-%d %H:%M:%S%.f %:z").ok()?;
+    Some(Entry {
+        id: 0,
+        amount: to_minor(&Money::from_decimal(amount, iso::USD)),
+        category: Category::new(&transaction.category),
+        time: time.with_timezone(&Local),
+        ..Default::default()
+    })
+}
+
+pub trait Storage: Send + Sync {
+    fn load(&self) -> io::Result<Option<Snapshot>>;
+    fn save(&self, snapshot: &Snapshot) -> io::Result<()>;
+}
+
+/// Keeps nothing. Used when no `--data` path is given, matching the old in-memory behavior.
+pub struct Memory;
+
+impl Storage for Memory {
+    fn load(&self) -> io::Result<Option<Snapshot>> {
+        Ok(None)
+    }
+
+    fn save(&self, _snapshot: &Snapshot) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stores the snapshot as a JSON document. Writes go to a sibling temp file that is flushed to
+/// disk and then renamed over the original, so a power cut mid-write leaves the previous copy
+/// intact.
+pub struct JsonFile {
+    path: PathBuf,
+}
+
+impl JsonFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Storage for JsonFile {
+    fn load(&self) -> io::Result<Option<Snapshot>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(snapshot).map_err(io::Error::other)?)?;
+        file.sync_all()?;
+        fs::rename(tmp, &self.path)?;
+        // the rename itself only lasts once the directory is on disk too
+        let dir = self
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        File::open(dir)?.sync_all()
+    }
+}
+
+/// Where the ledger called `name` is kept, next to the default ledger's `path`:
+/// `spending.json` becomes `spending.household.json`.
+pub fn ledger_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => {
+            path.with_file_name(format!("{stem}.{name}.{}", extension.to_string_lossy()))
+        }
+        None => path.with_file_name(format!("{stem}.{name}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_json_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("spending-{}.json", std::process::id()));
+        let storage = JsonFile::new(&path);
+        assert_eq!(storage.load().unwrap(), None);
+
+        let snapshot = Snapshot {
+            budget: 50000,
+            category_budgets: BTreeMap::from([(Category::Dining, 20000)]),
+            transactions: vec![Entry {
+                id: 1,
+                amount: 2478,
+                category: Category::Dining,
+                time: "2025-01-01T12:00:00-06:00".parse().unwrap(),
+                chat_id: Some(42),
+                ..Default::default()
+            }],
+            next_id: 2,
+            period_start: NaiveDate::from_ymd_opt(2025, 1, 1),
+            carried_over: 0,
+            periods: Vec::new(),
+            rules: Vec::new(),
+            recurring: Vec::new(),
+            next_recurring_id: 1,
+            history: Vec::new(),
+            alerts_sent: Vec::new(),
+            rates: Rates::new(),
+            categories: Categories::default(),
+            goals: Vec::new(),
+            ledgers: vec!["household".to_string()],
+            chat_ledgers: BTreeMap::from([(42, "household".to_string())]),
+        };
+        storage.save(&snapshot).unwrap();
+        assert_eq!(storage.load().unwrap(), Some(snapshot));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_load_listed_transactions() {
+        let path = std::env::temp_dir().join(format!("spending-old-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"budget":50000,"total":126478,"transactions":[
+                {"amount":"$24.78","category":"Dining","time":"2025-01-01 12:00:00 -06:00"},
+                {"amount":"$1,240.00","category":"Travel","time":"2025-01-02 08:30:00.123 -06:00"}
+            ]}"#,
+        )
+        .unwrap();
+        let snapshot = JsonFile::new(&path).load().unwrap().unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(snapshot.next_id, 0);
+        let entries = snapshot.transactions;
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].id, entries[0].amount), (0, 2478));
+        assert_eq!(entries[0].category, Category::Dining);
+        assert_eq!(
+            entries[0].time,
+            "2025-01-01T12:00:00-06:00"
+                .parse::<DateTime<Local>>()
+                .unwrap()
+        );
+        assert_eq!(entries[1].amount, 124000);
+    }
+
+    #[test]
+    fn test_ledger_path() {
+        assert_eq!(
+            ledger_path(Path::new("/data/spending.json"), "household"),
+            PathBuf::from("/data/spending.household.json")
+        );
+        assert_eq!(
+            ledger_path(Path::new("spending"), "trip"),
+            PathBuf::from("spending.trip")
+        );
+    }
+}
@@ -1,12 +1,14 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use telegram_chatbot::parser::{
-    is_spending_reset_request, is_spending_total_request, parse_budget_request,
-    parse_metro_request, parse_spending_request,
+    is_spending_reset_request, is_spending_total_request, is_spending_undo_request,
+    parse_budget_request, parse_ledger_request, parse_metro_request, parse_recurring_add_request,
+    parse_save_request, parse_spending_list_request, parse_spending_report_request,
+    parse_spending_request,
 };
 
 pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("parse metro req", |b| {
-        b.iter(|| parse_metro_request(black_box("West Cortex".to_string())));
+        b.iter(|| parse_metro_request(black_box("West Cortex red 3".to_string())));
     });
 
     c.bench_function("parse spending request", |b| {
@@ -24,6 +26,58 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("spending total request", |b| {
         b.iter(|| is_spending_total_request(black_box("Spent total".to_string())));
     });
+
+    c.bench_function("foreign spending request", |b| {
+        b.iter(|| parse_spending_request(black_box("Spent €12.50 dining".to_string())));
+    });
+
+    c.bench_function("spending request with details", |b| {
+        b.iter(|| {
+            parse_spending_request(black_box(
+                "Spent 47 other at hardware store #house \"shelf\"".to_string(),
+            ))
+        });
+    });
+
+    c.bench_function("split spending request", |b| {
+        b.iter(|| {
+            parse_spending_request(black_box("Spent 80 grocery 55 merchandise 25".to_string()))
+        });
+    });
+
+    c.bench_function("save request", |b| {
+        b.iter(|| parse_save_request(black_box("save 100 vacation".to_string())));
+    });
+
+    c.bench_function("ledger request", |b| {
+        b.iter(|| parse_ledger_request(black_box("ledger household".to_string())));
+    });
+
+    c.bench_function("income request", |b| {
+        b.iter(|| parse_spending_request(black_box("Earned 1200 salary".to_string())));
+    });
+
+    c.bench_function("spending list request", |b| {
+        b.iter(|| {
+            parse_spending_list_request(black_box("Spent list dining last week".to_string()))
+        });
+    });
+
+    c.bench_function("spending report request", |b| {
+        b.iter(|| parse_spending_report_request(black_box("Spent report week".to_string())));
+    });
+
+    c.bench_function("spending undo request", |b| {
+        b.iter(|| is_spending_undo_request(black_box("Spent undo".to_string())));
+    });
+
+    c.bench_function("recurring add request", |b| {
+        b.iter(|| {
+            parse_recurring_add_request(black_box(
+                "Recurring add 15.99 entertainment monthly 3".to_string(),
+            ))
+        });
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -24,15 +24,11 @@ pub struct Config {
 impl Config {
     pub fn from_env() -> Config {
         Self {
-            spending_api: Arc::new(env::var("SPENDING_API_URL").map_or(
-                SpendingAPI::default(),
-                |spending_base_url| SpendingAPI {
-                    spending_add_url: format!("{spending_base_url}/spent"),
-                    spending_total_url: format!("{spending_base_url}/spent"),
-                    spending_reset_url: format!("{spending_base_url}/reset"),
-                    budget_set_url: format!("{spending_base_url}/budget"),
-                },
-            )),
+            spending_api: Arc::new(
+                env::var("SPENDING_API_URL").map_or(SpendingAPI::default(), |spending_base_url| {
+                    SpendingAPI::new(&spending_base_url)
+                }),
+            ),
             metro_api: Arc::new(
                 env::var("METRO_API_URL")
                     .map_or(MetroScheduleAPI::default(), |url| MetroScheduleAPI { url }),
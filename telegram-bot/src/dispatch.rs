@@ -2,15 +2,23 @@ use std::sync::Arc;
 // use sysinfo::SystemExt;
 use crate::config::Config;
 use crate::parser::{
-    is_spending_reset_request, is_spending_total_request, parse_budget_request,
-    parse_metro_request, parse_spending_request,
+    is_recurring_list_request, is_spending_reset_request, is_spending_undo_request,
+    parse_budget_request, parse_ledger_request, parse_metro_request, parse_recurring_add_request,
+    parse_recurring_remove_request, parse_save_request, parse_spending_list_request,
+    parse_spending_report_request, parse_spending_request, parse_spending_total_query,
+    set_categories,
 };
+use crate::spending::is_statement;
 use metro_schedule::NextArrivalRequest;
 use simple_moving_average::{SumTreeSMA, SMA};
-use spending_tracker::SpentRequest;
+use spending_tracker::goals::SaveRequest;
+use spending_tracker::recurring::RecurringRequest;
+use spending_tracker::report::ReportPeriod;
+use spending_tracker::{SpentQuery, SpentRequest};
 use teloxide::dispatching::{HandlerExt, MessageFilterExt, UpdateFilterExt, UpdateHandler};
+use teloxide::net::Download;
 use teloxide::prelude::{ChatId, Message, Requester, Update};
-use teloxide::types::Location;
+use teloxide::types::{Document, Location, User};
 use teloxide::utils::command::BotCommands;
 use teloxide::{dptree, Bot};
 
@@ -65,6 +73,13 @@ enum Command {
 pub fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
     Update::filter_message()
         .branch(Message::filter_location().endpoint(weather_req))
+        .branch(
+            Message::filter_document()
+                .filter(|document: Document| {
+                    document.file_name.as_deref().is_some_and(is_statement)
+                })
+                .endpoint(statement_endpoint),
+        )
         .branch(
             dptree::entry()
                 .filter_command::<Command>()
@@ -82,23 +97,91 @@ pub fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'stat
         )
         .branch(
             Message::filter_text()
-                .filter(is_spending_total_request)
+                .filter(is_spending_undo_request)
+                .endpoint(spending_undo_endpoint),
+        )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_spending_total_query)
                 .endpoint(spending_total_endpoint),
         )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_spending_list_request)
+                .endpoint(spending_list_endpoint),
+        )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_spending_report_request)
+                .endpoint(spending_report_endpoint),
+        )
         .branch(
             Message::filter_text()
                 .filter_map(parse_spending_request)
                 .endpoint(spending_endpoint),
         )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_save_request)
+                .endpoint(save_endpoint),
+        )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_ledger_request)
+                .endpoint(ledger_endpoint),
+        )
         .branch(
             Message::filter_text()
                 .filter_map(parse_budget_request)
                 .endpoint(budget_endpoint),
         )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_recurring_add_request)
+                .endpoint(recurring_add_endpoint),
+        )
+        .branch(
+            Message::filter_text()
+                .filter_map(parse_recurring_remove_request)
+                .endpoint(recurring_remove_endpoint),
+        )
+        .branch(
+            Message::filter_text()
+                .filter(is_recurring_list_request)
+                .endpoint(recurring_list_endpoint),
+        )
 }
 
+/// Plain messages the bot understands besides the commands, with an example of each.
+const MESSAGES: &[&str] = &[
+    "east grand red 5 — the next trains from a station, optionally on one line and how many",
+    "spent 12.50 dining — log spending",
+    "spent total at costco #house — spending so far, optionally for a merchant or tag",
+    "spent reset — close the period and start over",
+    "budget 500 — set the budget",
+    "budget 200 dining — set a category's budget",
+    "spent undo — remove the last thing you logged",
+    "a .ofx, .qfx or .qif statement file — import its transactions",
+    "recurring add 15.99 entertainment monthly 3 — post an expense every month, or weekly, biweekly, every 3 weeks",
+    "recurring — list recurring expenses",
+    "recurring remove 2 — stop one",
+    "refund 20 merchandise — log money back",
+    "earned 1200 salary — log income",
+    "spent report week — this week or month against the last",
+    "spent 12.50 eur dining — log spending in another currency, or €12.50",
+    "spent 47 other at hardware store #house \"new shelf\" — log it with a merchant, tags and a note",
+    "spent list dining last 30 days — the latest entries, by category and today, yesterday, this or last week or month",
+    "spent 80 grocery 55 merchandise 25 — split spending across categories",
+    "ledger household — log this chat's spending to a shared ledger",
+    "save 100 vacation — put money toward a savings goal",
+];
+
 fn helpmsg() -> String {
-    Command::descriptions().to_string()
+    format!(
+        "{}\n\nYou can also send:\n{}",
+        Command::descriptions(),
+        MESSAGES.join("\n")
+    )
 }
 
 const WINDOW_SIZE: usize = 168;
@@ -136,6 +219,18 @@ pub async fn monitor_thermostat(config: Arc<Config>) {
     }
 }
 
+/// Keeps the parser's categories and aliases in line with spending-tracker's.
+pub async fn refresh_categories(config: Arc<Config>) {
+    const SLEEP_DURATION: u64 = 300;
+
+    loop {
+        if let Ok(categories) = config.spending_api.categories_request().await {
+            set_categories(&categories);
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(SLEEP_DURATION)).await;
+    }
+}
+
 async fn check_and_notify(
     config: &Arc<Config>,
     name: &str,
@@ -246,7 +341,7 @@ async fn spending_reset_endpoint(bot: Bot, msg: Message, config: Arc<Config>) ->
         msg.chat.id,
         config
             .spending_api
-            .spending_reset_request()
+            .spending_reset_request(msg.chat.id.0)
             .await
             .map_or("error calling spending api".to_string(), |resp| {
                 resp.to_string()
@@ -261,12 +356,17 @@ async fn spending_reset_endpoint(bot: Bot, msg: Message, config: Arc<Config>) ->
     Ok(())
 }
 
-async fn spending_total_endpoint(bot: Bot, msg: Message, config: Arc<Config>) -> HandlerResult {
+async fn spending_total_endpoint(
+    bot: Bot,
+    msg: Message,
+    query: SpentQuery,
+    config: Arc<Config>,
+) -> HandlerResult {
     bot.send_with_notification(
         msg.chat.id,
         config
             .spending_api
-            .spending_total_request()
+            .spending_total_request(msg.chat.id.0, query)
             .await
             .map_or("error calling spending api".to_string(), |resp| {
                 resp.to_string()
@@ -281,17 +381,185 @@ async fn spending_total_endpoint(bot: Bot, msg: Message, config: Arc<Config>) ->
     Ok(())
 }
 
+async fn spending_list_endpoint(
+    bot: Bot,
+    msg: Message,
+    query: SpentQuery,
+    config: Arc<Config>,
+) -> HandlerResult {
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .spending_total_request(msg.chat.id.0, query)
+            .await
+            .map_or("error calling spending api".to_string(), |resp| {
+                let mut reply = format!("{} transactions, {} total", resp.matched, resp.total);
+                for transaction in &resp.transactions {
+                    let date = transaction.time.get(..10).unwrap_or(&transaction.time);
+                    reply.push_str(&format!("\n{transaction} on {date}"));
+                }
+                reply
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn spending_report_endpoint(
+    bot: Bot,
+    msg: Message,
+    period: ReportPeriod,
+    config: Arc<Config>,
+) -> HandlerResult {
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .report_request(msg.chat.id.0, period)
+            .await
+            .map_or("error calling spending api".to_string(), |resp| {
+                resp.to_string()
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn statement_endpoint(
+    bot: Bot,
+    msg: Message,
+    document: Document,
+    config: Arc<Config>,
+) -> HandlerResult {
+    let file = bot.get_file(document.file.id).await?;
+    let mut statement = Vec::new();
+    bot.download_file(&file.path, &mut statement).await?;
+
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .statement_import_request(
+                msg.chat.id.0,
+                &document.file_name.unwrap_or_default(),
+                statement,
+            )
+            .await
+            .map_or("error importing statement".to_string(), |resp| {
+                resp.to_string()
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn spending_undo_endpoint(bot: Bot, msg: Message, config: Arc<Config>) -> HandlerResult {
+    bot.send_with_notification(
+        msg.chat.id,
+        match config
+            .spending_api
+            .undo_request(msg.chat.id.0, msg.from.as_ref().map(user_id))
+            .await
+        {
+            Ok(resp) => resp.to_string(),
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                "nothing to undo".to_string()
+            }
+            Err(_) => "error calling spending api".to_string(),
+        },
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
 async fn spending_endpoint(
     bot: Bot,
     msg: Message,
-    req: SpentRequest,
+    mut req: SpentRequest,
+    config: Arc<Config>,
+) -> HandlerResult {
+    req.chat_id = Some(msg.chat.id.0);
+    if let Some(user) = &msg.from {
+        req.user_id = Some(user_id(user));
+        req.user_name = Some(user.first_name.clone());
+    }
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .spending_request(msg.chat.id.0, req)
+            .await
+            .map_or("error calling spending api".to_string(), |resp| {
+                resp.to_string()
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn save_endpoint(
+    bot: Bot,
+    msg: Message,
+    req: SaveRequest,
+    config: Arc<Config>,
+) -> HandlerResult {
+    let goal = req.goal.clone();
+    bot.send_with_notification(
+        msg.chat.id,
+        match config.spending_api.save_request(msg.chat.id.0, req).await {
+            Ok(resp) => resp.to_string(),
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                format!("no savings goal called {goal}")
+            }
+            Err(_) => "error calling spending api".to_string(),
+        },
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn ledger_endpoint(
+    bot: Bot,
+    msg: Message,
+    name: String,
     config: Arc<Config>,
 ) -> HandlerResult {
     bot.send_with_notification(
         msg.chat.id,
         config
             .spending_api
-            .spending_request(req)
+            .ledger_request(msg.chat.id.0, name)
             .await
             .map_or("error calling spending api".to_string(), |resp| {
                 resp.to_string()
@@ -306,6 +574,11 @@ async fn spending_endpoint(
     Ok(())
 }
 
+/// Telegram user ids fit in 52 bits, so they are stored signed like chat ids.
+fn user_id(user: &User) -> i64 {
+    user.id.0 as i64
+}
+
 async fn budget_endpoint(
     bot: Bot,
     msg: Message,
@@ -316,7 +589,77 @@ async fn budget_endpoint(
         msg.chat.id,
         config
             .spending_api
-            .budget_set_request(req)
+            .budget_set_request(msg.chat.id.0, req)
+            .await
+            .map_or("error calling spending api".to_string(), |resp| {
+                resp.to_string()
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn recurring_add_endpoint(
+    bot: Bot,
+    msg: Message,
+    req: RecurringRequest,
+    config: Arc<Config>,
+) -> HandlerResult {
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .recurring_add_request(msg.chat.id.0, req)
+            .await
+            .map_or("error calling spending api".to_string(), |resp| {
+                format!("added {}", resp)
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn recurring_remove_endpoint(
+    bot: Bot,
+    msg: Message,
+    id: u64,
+    config: Arc<Config>,
+) -> HandlerResult {
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .recurring_remove_request(msg.chat.id.0, id)
+            .await
+            .map_or("error calling spending api".to_string(), |resp| {
+                resp.to_string()
+            }),
+        config
+            .notification_service
+            .has_notifications()
+            .await
+            .unwrap_or(false),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn recurring_list_endpoint(bot: Bot, msg: Message, config: Arc<Config>) -> HandlerResult {
+    bot.send_with_notification(
+        msg.chat.id,
+        config
+            .spending_api
+            .recurring_list_request(msg.chat.id.0)
             .await
             .map_or("error calling spending api".to_string(), |resp| {
                 resp.to_string()
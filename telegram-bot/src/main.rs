@@ -1,5 +1,7 @@
 use std::sync::Arc;
-use telegram_chatbot::{config::Config, dispatch::monitor_thermostat, dispatch::schema};
+use telegram_chatbot::{
+    config::Config, dispatch::monitor_thermostat, dispatch::refresh_categories, dispatch::schema,
+};
 use teloxide::prelude::*;
 
 #[tokio::main]
@@ -7,6 +9,7 @@ async fn main() {
     //TODO: re-add prometheus metrics
     let config = Arc::new(Config::from_env());
     tokio::spawn(monitor_thermostat(config.clone()));
+    tokio::spawn(refresh_categories(config.clone()));
 
     // run_webserver(&config, prometheus);
     run_chatbot(config).await;
@@ -1,40 +1,193 @@
-use metro_schedule::{Direction, NextArrivalRequest, Station};
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use metro_schedule::{Direction, Line, NextArrivalRequest, Station};
+use std::sync::{LazyLock, RwLock};
 use winnow::ascii::{digit0, digit1, space0, space1, Caseless};
-use winnow::combinator::{alt, opt, preceded, separated_pair};
-use winnow::token::literal;
+use winnow::combinator::{
+    alt, delimited, eof, opt, peek, preceded, repeat, separated_pair, terminated,
+};
+use winnow::error::{ContextError, ParserError};
+use winnow::token::{literal, rest, take_till, take_while};
 use winnow::{Parser, Result};
 
-use spending_tracker::{Category, SpentRequest};
+use spending_tracker::categories::Categories;
+use spending_tracker::goals::SaveRequest;
+use spending_tracker::recurring::{RecurringRequest, Schedule};
+use spending_tracker::report::ReportPeriod;
+use spending_tracker::{
+    currency, normalize_tag, Category, Kind, Sort, SpentQuery, SpentRequest, SplitRequest,
+};
+
+/// How many departures the bot lists when the message doesn't say.
+const DEFAULT_DEPARTURES: usize = 3;
 
 pub fn parse_metro_request(s: String) -> Option<NextArrivalRequest> {
-    parse_station_and_direction(&mut s.as_str())
+    (
+        parse_station_and_direction,
+        opt(preceded(space1, parse_line)),
+        opt(preceded(space1, digit1.try_map(str::parse))),
+    )
+        .parse_next(&mut s.as_str())
         .ok()
-        .map(|(direction, station)| NextArrivalRequest { station, direction })
+        .map(|((direction, station), line, count)| NextArrivalRequest {
+            station,
+            direction,
+            count: count.unwrap_or(DEFAULT_DEPARTURES),
+            line,
+        })
 }
 
 pub fn parse_spending_request(s: String) -> Option<SpentRequest> {
-    parse_amount_and_category(&mut s.as_str())
+    parse_split_amount(&mut s.as_str())
         .ok()
-        .map(|(amount, category)| SpentRequest { category, amount })
+        .map(|(amount, splits, details)| SpentRequest {
+            amount,
+            splits,
+            ..details.into()
+        })
+        .or_else(|| {
+            parse_foreign_amount_and_category(&mut s.as_str()).ok().map(
+                |(currency, amount, category, details)| SpentRequest {
+                    category,
+                    amount,
+                    currency: Some(currency.to_string()),
+                    ..details.into()
+                },
+            )
+        })
+        .or_else(|| {
+            parse_amount_and_category(&mut s.as_str())
+                .ok()
+                .map(|(amount, category, details)| SpentRequest {
+                    category,
+                    amount,
+                    ..details.into()
+                })
+        })
+        .or_else(|| {
+            parse_refund(&mut s.as_str())
+                .ok()
+                .map(|(amount, category)| SpentRequest {
+                    amount,
+                    category,
+                    kind: Kind::Refund,
+                    ..Default::default()
+                })
+        })
+        .or_else(|| {
+            parse_income(&mut s.as_str())
+                .ok()
+                .map(|(amount, source)| SpentRequest {
+                    amount,
+                    kind: Kind::Income,
+                    source,
+                    ..Default::default()
+                })
+        })
 }
 
 pub fn parse_budget_request(s: String) -> Option<SpentRequest> {
-    parse_budget_and_amount(&mut s.as_str())
+    parse_budget_amount_and_category(&mut s.as_str())
         .ok()
-        .map(|amount| SpentRequest {
+        .map(|(amount, category)| SpentRequest {
             amount,
-            category: None,
+            category: Some(category),
+            ..Default::default()
+        })
+        .or_else(|| {
+            parse_budget_and_amount(&mut s.as_str())
+                .ok()
+                .map(|amount| SpentRequest {
+                    amount,
+                    ..Default::default()
+                })
         })
 }
 
+pub fn parse_recurring_add_request(s: String) -> Option<RecurringRequest> {
+    parse_recurring_add(&mut s.as_str())
+        .ok()
+        .map(|(amount, category, schedule)| RecurringRequest {
+            amount,
+            category,
+            schedule,
+            start: None,
+        })
+}
+
+pub fn parse_recurring_remove_request(s: String) -> Option<u64> {
+    parse_recurring_remove(&mut s.as_str()).ok()
+}
+
+pub fn is_recurring_list_request(s: String) -> bool {
+    parse_recurring_list(&mut s.as_str()).is_ok()
+}
+
+/// `spent list [category] [span] [at merchant] [#tag]`, e.g. `spent list dining last week`.
+/// Lists the newest matches first.
+pub fn parse_spending_list_request(s: String) -> Option<SpentQuery> {
+    const LIST_LIMIT: usize = 10;
+
+    parse_spending_list(&mut s.as_str())
+        .ok()
+        .map(|(category, span, details)| {
+            let (from, to) = span
+                .map(|span| span.dates(Local::now().date_naive()))
+                .unzip();
+            SpentQuery {
+                category: category.map(|category| category.to_string()),
+                from,
+                to,
+                merchant: details.merchant,
+                tag: details.tags.into_iter().next(),
+                q: details.note,
+                sort: Some(Sort::Newest),
+                limit: Some(LIST_LIMIT),
+                ..Default::default()
+            }
+        })
+}
+
+pub fn parse_spending_report_request(s: String) -> Option<ReportPeriod> {
+    parse_spending_report(&mut s.as_str()).ok()
+}
+
 pub fn is_spending_total_request(s: String) -> bool {
     parse_spending_total_request(&mut s.as_str()).is_ok()
 }
 
+/// `spent total`, optionally narrowed with `at <merchant>` and a `#tag`.
+pub fn parse_spending_total_query(s: String) -> Option<SpentQuery> {
+    preceded(parse_spending_total_request, parse_details)
+        .parse_next(&mut s.as_str())
+        .ok()
+        .map(|details| SpentQuery {
+            tag: details.tags.into_iter().next(),
+            merchant: details.merchant,
+            ..Default::default()
+        })
+}
+
+pub fn parse_save_request(s: String) -> Option<SaveRequest> {
+    parse_save(&mut s.as_str())
+        .ok()
+        .map(|(amount, goal)| SaveRequest {
+            goal: goal.trim().to_string(),
+            amount,
+        })
+}
+
+pub fn parse_ledger_request(s: String) -> Option<String> {
+    parse_ledger(&mut s.as_str()).ok().map(str::to_lowercase)
+}
+
 pub fn is_spending_reset_request(s: String) -> bool {
     parse_spending_reset_request(&mut s.as_str()).is_ok()
 }
 
+pub fn is_spending_undo_request(s: String) -> bool {
+    parse_spending_undo_request(&mut s.as_str()).is_ok()
+}
+
 fn parse_spending_total_request<'s>(s: &mut &'s str) -> Result<(&'s str, &'s str)> {
     separated_pair(
         literal(Caseless("spent")),
@@ -44,6 +197,136 @@ fn parse_spending_total_request<'s>(s: &mut &'s str) -> Result<(&'s str, &'s str
     .parse_next(s)
 }
 
+fn parse_spending_list(s: &mut &str) -> Result<(Option<Category>, Option<Span>, Details)> {
+    preceded(
+        (
+            literal(Caseless("spent")),
+            space1,
+            literal(Caseless("list")),
+        ),
+        terminated(
+            (
+                opt(preceded(space1, parse_category)),
+                opt(preceded(space1, parse_span)),
+                parse_details,
+            ),
+            (space0, eof),
+        ),
+    )
+    .parse_next(s)
+}
+
+/// Days relative to today.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Span {
+    Today,
+    Yesterday,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+    /// The last n days, today included.
+    Days(u64),
+}
+
+impl Span {
+    /// First and last day of the span.
+    fn dates(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let monday = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+        let first = today.with_day(1).unwrap_or(today);
+        match self {
+            Self::Today => (today, today),
+            Self::Yesterday => (today - Days::new(1), today - Days::new(1)),
+            Self::ThisWeek => (monday, today),
+            Self::LastWeek => (monday - Days::new(7), monday - Days::new(1)),
+            Self::ThisMonth => (first, today),
+            Self::LastMonth => (first - Months::new(1), first - Days::new(1)),
+            Self::Days(n) => (
+                today
+                    .checked_sub_days(Days::new(n.max(1) - 1))
+                    .unwrap_or(NaiveDate::MIN),
+                today,
+            ),
+        }
+    }
+}
+
+/// Longest `last n days` span accepted, about a century.
+const MAX_SPAN_DAYS: u64 = 36_525;
+
+fn parse_span(s: &mut &str) -> Result<Span> {
+    alt((
+        literal(Caseless("today")).value(Span::Today),
+        literal(Caseless("yesterday")).value(Span::Yesterday),
+        preceded(
+            (literal(Caseless("this")), space1),
+            alt((
+                literal(Caseless("week")).value(Span::ThisWeek),
+                literal(Caseless("month")).value(Span::ThisMonth),
+            )),
+        ),
+        preceded(
+            (literal(Caseless("last")), space1),
+            alt((
+                literal(Caseless("week")).value(Span::LastWeek),
+                literal(Caseless("month")).value(Span::LastMonth),
+                terminated(
+                    digit1
+                        .try_map(str::parse)
+                        .verify(|n: &u64| *n <= MAX_SPAN_DAYS),
+                    (space1, literal(Caseless("days"))),
+                )
+                .map(Span::Days),
+            )),
+        ),
+    ))
+    .parse_next(s)
+}
+
+// spent report [week|month]
+fn parse_spending_report(s: &mut &str) -> Result<ReportPeriod> {
+    preceded(
+        (
+            literal(Caseless("spent")),
+            space1,
+            literal(Caseless("report")),
+        ),
+        terminated(
+            opt(preceded(
+                space1,
+                alt((
+                    literal(Caseless("week")).value(ReportPeriod::Week),
+                    literal(Caseless("month")).value(ReportPeriod::Month),
+                )),
+            ))
+            .map(Option::unwrap_or_default),
+            (space0, eof),
+        ),
+    )
+    .parse_next(s)
+}
+
+// save 100 vacation
+fn parse_save<'s>(s: &mut &'s str) -> Result<(f32, &'s str)> {
+    preceded(
+        (literal(Caseless("save")), space1, opt('$')),
+        separated_pair(parse_price, space1, take_while(1.., |c: char| c != '\n')),
+    )
+    .parse_next(s)
+}
+
+// ledger household
+fn parse_ledger<'s>(s: &mut &'s str) -> Result<&'s str> {
+    delimited(
+        (literal(Caseless("ledger")), space1),
+        take_while(1..=32, |c: char| {
+            c.is_ascii_alphanumeric() || c == '-' || c == '_'
+        }),
+        (space0, eof),
+    )
+    .parse_next(s)
+}
+
 fn parse_spending_reset_request<'s>(s: &mut &'s str) -> Result<(&'s str, &'s str)> {
     (separated_pair(
         literal(Caseless("spent")),
@@ -53,18 +336,240 @@ fn parse_spending_reset_request<'s>(s: &mut &'s str) -> Result<(&'s str, &'s str
     .parse_next(s)
 }
 
-fn parse_amount_and_category(s: &mut &str) -> Result<(f32, Option<Category>)> {
+fn parse_spending_undo_request<'s>(s: &mut &'s str) -> Result<&'s str> {
+    delimited(
+        opt((literal(Caseless("spent")), space1)),
+        literal(Caseless("undo")),
+        (space0, eof),
+    )
+    .parse_next(s)
+}
+
+/// Optional extras that can follow a spending amount and category.
+#[derive(Debug, Default, PartialEq)]
+struct Details {
+    merchant: Option<String>,
+    tags: Vec<String>,
+    note: Option<String>,
+}
+
+impl From<Details> for SpentRequest {
+    fn from(details: Details) -> Self {
+        SpentRequest {
+            merchant: details.merchant,
+            tags: details.tags,
+            note: details.note,
+            ..Default::default()
+        }
+    }
+}
+
+// spent 47 other at hardware store #house "new shelf"
+fn parse_amount_and_category(s: &mut &str) -> Result<(f32, Option<Category>, Details)> {
+    delimited(
+        literal(Caseless("spent ")),
+        (
+            terminated(parse_price, space0),
+            opt(parse_category),
+            parse_details,
+        ),
+        (space0, eof),
+    )
+    .parse_next(s)
+}
+
+// spent 80 grocery 55 merchandise 25
+fn parse_split_amount(s: &mut &str) -> Result<(f32, Vec<SplitRequest>, Details)> {
     preceded(
         literal(Caseless("spent ")),
+        (
+            parse_price,
+            repeat(
+                2..,
+                preceded(space1, separated_pair(parse_category, space1, parse_price))
+                    .map(|(category, amount)| SplitRequest { category, amount }),
+            ),
+            parse_details,
+        ),
+    )
+    .parse_next(s)
+}
+
+// spent 12.50 EUR dining, spent €12.50 dining
+fn parse_foreign_amount_and_category<'s>(
+    s: &mut &'s str,
+) -> Result<(&'s str, f32, Option<Category>, Details)> {
+    delimited(
+        literal(Caseless("spent ")),
+        (
+            alt((
+                (parse_currency_symbol, terminated(parse_price, space0)),
+                (
+                    terminated(parse_price, space0),
+                    terminated(parse_currency_code, space0),
+                )
+                    .map(|(amount, currency)| (currency, amount)),
+            )),
+            opt(parse_category),
+            parse_details,
+        )
+            .map(|((currency, amount), category, details)| (currency, amount, category, details)),
+        (space0, eof),
+    )
+    .parse_next(s)
+}
+
+// [at <merchant>] [#tag ...] ["note"]
+fn parse_details(s: &mut &str) -> Result<Details> {
+    (
+        opt(preceded(
+            (space0, literal(Caseless("at")), space1),
+            take_till(1.., ['#', '"', '“']),
+        )),
+        repeat(
+            0..,
+            preceded(
+                (space0, '#'),
+                take_while(1.., |c: char| c.is_alphanumeric() || c == '-' || c == '_'),
+            ),
+        ),
+        opt(preceded(
+            (space0, alt(('"', '“'))),
+            terminated(take_till(0.., ['"', '”']), alt(('"', '”'))),
+        )),
+    )
+        .map(
+            |(merchant, tags, note): (Option<&str>, Vec<&str>, Option<&str>)| Details {
+                merchant: merchant
+                    .map(str::trim)
+                    .filter(|m| !m.is_empty())
+                    .map(str::to_string),
+                tags: tags.into_iter().map(normalize_tag).collect(),
+                note: note
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .map(str::to_string),
+            },
+        )
+        .parse_next(s)
+}
+
+fn parse_currency_symbol<'s>(s: &mut &'s str) -> Result<&'s str> {
+    alt((
+        literal("€").value("EUR"),
+        literal("£").value("GBP"),
+        literal("¥").value("JPY"),
+        literal("C$").value("CAD"),
+        literal("MX$").value("MXN"),
+        literal("$").value("USD"),
+    ))
+    .parse_next(s)
+}
+
+// in any case, unless the word is also a category
+fn parse_currency_code(s: &mut &str) -> Result<&'static str> {
+    terminated(
+        take_while(3, |c: char| c.is_ascii_alphabetic()),
+        peek(alt((space1, eof))),
+    )
+    .verify(|word: &str| parse_category(&mut &*word).is_err())
+    .verify_map(currency::find)
+    .map(|currency| currency.iso_alpha_code)
+    .parse_next(s)
+}
+
+// refund 20 merchandise
+fn parse_refund(s: &mut &str) -> Result<(f32, Option<Category>)> {
+    preceded(
+        literal(Caseless("refund ")),
         separated_pair(parse_price, space0, opt(parse_category)),
     )
     .parse_next(s)
 }
 
+// earned 1200 salary
+fn parse_income(s: &mut &str) -> Result<(f32, Option<String>)> {
+    preceded(
+        literal(Caseless("earned ")),
+        separated_pair(
+            parse_price,
+            space0,
+            rest.map(|source: &str| {
+                let source = source.trim();
+                (!source.is_empty()).then(|| source.to_string())
+            }),
+        ),
+    )
+    .parse_next(s)
+}
+
+// recurring add 15.99 entertainment monthly 3
+fn parse_recurring_add(s: &mut &str) -> Result<(f32, Option<Category>, Schedule)> {
+    preceded(
+        literal(Caseless("recurring add ")),
+        (
+            terminated(parse_price, space1),
+            opt(terminated(parse_category, space1)),
+            parse_schedule,
+        ),
+    )
+    .parse_next(s)
+}
+
+fn parse_recurring_remove(s: &mut &str) -> Result<u64> {
+    preceded(
+        (
+            literal(Caseless("recurring")),
+            space1,
+            alt((literal(Caseless("remove")), literal(Caseless("delete")))),
+            space1,
+        ),
+        digit1.try_map(str::parse),
+    )
+    .parse_next(s)
+}
+
+fn parse_recurring_list<'s>(s: &mut &'s str) -> Result<&'s str> {
+    terminated(
+        literal(Caseless("recurring")),
+        (opt((space1, literal(Caseless("list")))), space0, eof),
+    )
+    .parse_next(s)
+}
+
+fn parse_schedule(s: &mut &str) -> Result<Schedule> {
+    alt((
+        preceded(
+            (literal(Caseless("monthly")), space1),
+            digit1.try_map(str::parse),
+        )
+        .map(|day| Schedule::Monthly { day }),
+        preceded(
+            (literal(Caseless("every")), space1),
+            terminated(
+                digit1.try_map(str::parse),
+                (space1, literal(Caseless("weeks"))),
+            ),
+        )
+        .map(|every| Schedule::Weekly { every }),
+        literal(Caseless("biweekly")).value(Schedule::Weekly { every: 2 }),
+        literal(Caseless("weekly")).value(Schedule::Weekly { every: 1 }),
+    ))
+    .parse_next(s)
+}
+
 fn parse_budget_and_amount(s: &mut &str) -> Result<f32> {
     preceded(literal(Caseless("budget ")), parse_price).parse_next(s)
 }
 
+fn parse_budget_amount_and_category(s: &mut &str) -> Result<(f32, Category)> {
+    preceded(
+        literal(Caseless("budget ")),
+        separated_pair(parse_price, space1, parse_category),
+    )
+    .parse_next(s)
+}
+
 // d+.?d*
 fn parse_price(s: &mut &str) -> Result<f32> {
     (digit1, opt('.'), digit0)
@@ -85,6 +590,14 @@ fn parse_direction(s: &mut &str) -> Result<Direction> {
     .parse_next(s)
 }
 
+fn parse_line(s: &mut &str) -> Result<Line> {
+    alt((
+        literal(Caseless("red")).value(Line::Red),
+        literal(Caseless("blue")).value(Line::Blue),
+    ))
+    .parse_next(s)
+}
+
 fn parse_station(s: &mut &str) -> Result<Station> {
     alt((
         literal(Caseless("lambert2")).value(Station::LambertT2),
@@ -143,25 +656,96 @@ fn parse_station(s: &mut &str) -> Result<Station> {
     .parse_next(s)
 }
 
+/// Words the parser recognises as categories, longest first so `groceries` wins over
+/// `grocery`. Starts out with the built-in categories until spending-tracker is asked.
+static CATEGORY_WORDS: LazyLock<RwLock<Vec<(String, Category)>>> =
+    LazyLock::new(|| RwLock::new(category_words(&Categories::default())));
+
+fn category_words(categories: &Categories) -> Vec<(String, Category)> {
+    let mut words: Vec<(String, Category)> = categories
+        .0
+        .iter()
+        .filter(|info| !info.archived)
+        .flat_map(|info| {
+            std::iter::once(info.name.name())
+                .chain(info.aliases.iter().map(String::as_str))
+                .map(|word| (word.to_lowercase(), info.name.clone()))
+        })
+        .collect();
+    words.sort_by_key(|(word, _)| std::cmp::Reverse(word.len()));
+    words
+}
+
+/// Replaces the categories and aliases the parser matches against.
+pub fn set_categories(categories: &Categories) {
+    if let Ok(mut words) = CATEGORY_WORDS.write() {
+        *words = category_words(categories);
+    }
+}
+
 fn parse_category(s: &mut &str) -> Result<Category> {
-    alt((
-        literal(Caseless("dining")).value(Category::Dining),
-        literal(Caseless("grocery")).value(Category::Grocery),
-        literal(Caseless("merchandise")).value(Category::Merchandise),
-        literal(Caseless("travel")).value(Category::Travel),
-        literal(Caseless("entertainment")).value(Category::Entertainment),
-        literal(Caseless("other")).value(Category::Other),
-    ))
-    .parse_next(s)
+    let words = CATEGORY_WORDS
+        .read()
+        .map_err(|_| ContextError::from_input(s))?;
+    for (word, category) in words.iter() {
+        let matched = s
+            .get(..word.len())
+            .is_some_and(|prefix| prefix.to_lowercase() == *word);
+        if !matched {
+            continue;
+        }
+        // `word.len()` may fall inside a character that only starts like the word
+        let Some(rest) = s.get(word.len()..) else {
+            continue;
+        };
+        if rest.chars().next().is_none_or(|c| !c.is_alphanumeric()) {
+            *s = rest;
+            return Ok(category.clone());
+        }
+    }
+    Err(ContextError::from_input(s))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use spending_tracker::categories::CategoryInfo;
 
     #[test]
     fn test_parse_category() {
-        assert_eq!(parse_category(&mut "DINING").unwrap(), Category::Dining)
+        assert_eq!(parse_category(&mut "DINING").unwrap(), Category::Dining);
+        assert_eq!(parse_category(&mut "food").unwrap(), Category::Dining);
+        assert_eq!(parse_category(&mut "groceries").unwrap(), Category::Grocery);
+        assert!(parse_category(&mut "diningroom").is_err());
+        assert!(parse_category(&mut "café").is_err());
+        assert!(parse_category(&mut "🍕").is_err());
+        assert!(parse_category(&mut "foodé").is_err());
+        assert_eq!(parse_category(&mut "food 🍕").unwrap(), Category::Dining);
+        // an unknown category isn't dropped for Other
+        assert!(parse_spending_request("spent 5 café".to_string()).is_none());
+        assert!(parse_spending_request("spent 5 🍕".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_set_categories() {
+        let mut categories = Categories::default();
+        categories
+            .create(CategoryInfo {
+                name: Category::new("Pets"),
+                aliases: vec!["dog".to_string()],
+                archived: false,
+            })
+            .unwrap();
+        set_categories(&categories);
+        let dog = parse_category(&mut "Dog");
+        let dining = parse_category(&mut "dining");
+        let req = parse_spending_request("spent 30 pets at vet".to_string());
+        // the words are shared with every other test, so put the defaults back before asserting
+        set_categories(&Categories::default());
+        assert_eq!(dog.unwrap(), Category::new("Pets"));
+        assert_eq!(dining.unwrap(), Category::Dining);
+        assert_eq!(req.unwrap().category, Some(Category::new("Pets")));
+        assert!(parse_category(&mut "dog").is_err());
     }
 
     #[test]
@@ -188,40 +772,333 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parse_metro_request() {
+        assert_eq!(
+            parse_metro_request("west cortex".to_string()).unwrap(),
+            NextArrivalRequest {
+                station: Station::Cortex,
+                direction: Direction::West,
+                count: DEFAULT_DEPARTURES,
+                line: None,
+            }
+        );
+        assert_eq!(
+            parse_metro_request("East CWE blue 5".to_string()).unwrap(),
+            NextArrivalRequest {
+                station: Station::CWE,
+                direction: Direction::East,
+                count: 5,
+                line: Some(Line::Blue),
+            }
+        );
+        let req = parse_metro_request("west delmar 2".to_string()).unwrap();
+        assert_eq!((req.count, req.line), (2, None));
+        let req = parse_metro_request("west delmar red".to_string()).unwrap();
+        assert_eq!((req.count, req.line), (DEFAULT_DEPARTURES, Some(Line::Red)));
+    }
+
+    #[test]
+    fn test_parse_foreign_amount_and_category() {
+        assert_eq!(
+            parse_foreign_amount_and_category(&mut "spent 12.50 EUR dining").unwrap(),
+            ("EUR", 12.5f32, Some(Category::Dining), Details::default())
+        );
+        assert_eq!(
+            parse_foreign_amount_and_category(&mut "spent €12.50 dining").unwrap(),
+            ("EUR", 12.5f32, Some(Category::Dining), Details::default())
+        );
+        assert_eq!(
+            parse_foreign_amount_and_category(&mut "spent MX$200").unwrap(),
+            ("MXN", 200f32, None, Details::default())
+        );
+        assert!(parse_foreign_amount_and_category(&mut "spent 12.50 dining").is_err());
+        assert!(parse_foreign_amount_and_category(&mut "spent 12.50 XYZ").is_err());
+
+        let req = parse_spending_request("spent 40 CAD travel".to_string()).unwrap();
+        assert_eq!(
+            (req.amount, req.currency.as_deref(), req.category),
+            (40f32, Some("CAD"), Some(Category::Travel))
+        );
+        let req = parse_spending_request("spent 24.78 dining".to_string()).unwrap();
+        assert_eq!(req.currency, None);
+
+        let req = parse_spending_request("spent 12.50 eur dining".to_string()).unwrap();
+        assert_eq!(
+            (req.amount, req.currency.as_deref(), req.category),
+            (12.5f32, Some("EUR"), Some(Category::Dining))
+        );
+        // leftover text is rejected rather than dropped
+        assert!(parse_spending_request("spent 12.50 xyz dining".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_parse_save() {
+        assert_eq!(
+            parse_save_request("Save $100 new car ".to_string()),
+            Some(SaveRequest {
+                goal: "new car".to_string(),
+                amount: 100f32
+            })
+        );
+        assert_eq!(
+            parse_save(&mut "save 12.50 vacation").unwrap(),
+            (12.5f32, "vacation")
+        );
+        assert!(parse_save(&mut "save 100").is_err());
+        assert!(parse_save(&mut "save vacation").is_err());
+    }
+
+    #[test]
+    fn test_parse_ledger() {
+        assert_eq!(
+            parse_ledger_request("Ledger Household".to_string()),
+            Some("household".to_string())
+        );
+        assert_eq!(parse_ledger(&mut "ledger trip-2025 ").unwrap(), "trip-2025");
+        assert!(parse_ledger(&mut "ledger").is_err());
+        assert!(parse_ledger(&mut "ledger our house").is_err());
+    }
+
+    #[test]
+    fn test_parse_split_amount() {
+        assert_eq!(
+            parse_split_amount(&mut "spent 80 grocery 55 merchandise 25").unwrap(),
+            (
+                80f32,
+                vec![
+                    SplitRequest {
+                        category: Category::Grocery,
+                        amount: 55f32
+                    },
+                    SplitRequest {
+                        category: Category::Merchandise,
+                        amount: 25f32
+                    },
+                ],
+                Details::default()
+            )
+        );
+        assert!(parse_split_amount(&mut "spent 80 grocery 55").is_err());
+        assert!(parse_split_amount(&mut "spent 80 grocery").is_err());
+
+        let req =
+            parse_spending_request("spent 80 groceries 55 fun 25 at costco #bulk".to_string())
+                .unwrap();
+        assert_eq!(
+            (req.amount, req.category, req.merchant.as_deref()),
+            (80f32, None, Some("costco"))
+        );
+        assert_eq!(
+            req.splits,
+            vec![
+                SplitRequest {
+                    category: Category::Grocery,
+                    amount: 55f32
+                },
+                SplitRequest {
+                    category: Category::Entertainment,
+                    amount: 25f32
+                },
+            ]
+        );
+        assert!(parse_spending_request("spent 80 grocery".to_string())
+            .unwrap()
+            .splits
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_refund_and_income() {
+        assert_eq!(
+            parse_refund(&mut "refund 20 merchandise").unwrap(),
+            (20f32, Some(Category::Merchandise))
+        );
+        assert_eq!(
+            parse_income(&mut "earned 1200 salary").unwrap(),
+            (1200f32, Some("salary".to_string()))
+        );
+        assert_eq!(parse_income(&mut "earned 50").unwrap(), (50f32, None));
+
+        let req = parse_spending_request("Refund 20 merchandise".to_string()).unwrap();
+        assert_eq!(
+            (req.amount, req.category, req.kind),
+            (20f32, Some(Category::Merchandise), Kind::Refund)
+        );
+        let req = parse_spending_request("earned 1200 salary".to_string()).unwrap();
+        assert_eq!(
+            (req.kind, req.source.as_deref()),
+            (Kind::Income, Some("salary"))
+        );
+        assert_eq!(
+            parse_spending_request("spent 5 dining".to_string()).map(|req| req.kind),
+            Some(Kind::Expense)
+        );
+    }
+
+    #[test]
+    fn test_parse_spending_list() {
+        assert_eq!(
+            parse_spending_list(&mut "spent list dining last week").unwrap(),
+            (
+                Some(Category::Dining),
+                Some(Span::LastWeek),
+                Details::default()
+            )
+        );
+        assert_eq!(
+            parse_spending_list(&mut "spent list last 30 days #house").unwrap(),
+            (
+                None,
+                Some(Span::Days(30)),
+                Details {
+                    tags: vec!["house".to_string()],
+                    ..Default::default()
+                }
+            )
+        );
+        let query = parse_spending_list_request("spent list".to_string()).unwrap();
+        assert_eq!((query.from, query.sort), (None, Some(Sort::Newest)));
+        assert!(parse_spending_list_request("spent 5".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_span_dates() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        // a Wednesday
+        let today = date(3, 12);
+        assert_eq!(Span::LastWeek.dates(today), (date(3, 3), date(3, 9)));
+        assert_eq!(Span::ThisWeek.dates(today), (date(3, 10), today));
+        assert_eq!(Span::LastMonth.dates(today), (date(2, 1), date(2, 28)));
+        assert_eq!(Span::Days(7).dates(today), (date(3, 6), today));
+        assert_eq!(Span::Days(u64::MAX).dates(today), (NaiveDate::MIN, today));
+        assert!(parse_span(&mut "last 999999999999 days").is_err());
+        let query = parse_spending_list_request("spent list last 999999999999 days".to_string());
+        assert!(query.is_none());
+        assert_eq!(Span::Yesterday.dates(today), (date(3, 11), date(3, 11)));
+    }
+
+    #[test]
+    fn test_parse_spending_report() {
+        assert_eq!(
+            parse_spending_report_request("spent report".to_string()),
+            Some(ReportPeriod::Month)
+        );
+        assert_eq!(
+            parse_spending_report_request("Spent report week".to_string()),
+            Some(ReportPeriod::Week)
+        );
+        assert_eq!(
+            parse_spending_report_request("spent report year".to_string()),
+            None
+        );
+    }
+
     #[test]
     fn test_parse_budget_and_amount() {
         assert_eq!(parse_budget_and_amount(&mut "budget 500").unwrap(), 500f32)
     }
+
+    #[test]
+    fn test_parse_budget_amount_and_category() {
+        assert_eq!(
+            parse_budget_amount_and_category(&mut "budget 200 dining").unwrap(),
+            (200f32, Category::Dining)
+        );
+        assert!(parse_budget_amount_and_category(&mut "budget 200").is_err());
+
+        let req = parse_budget_request("budget 200 dining".to_string()).unwrap();
+        assert_eq!((req.amount, req.category), (200f32, Some(Category::Dining)));
+        let req = parse_budget_request("budget 500".to_string()).unwrap();
+        assert_eq!((req.amount, req.category), (500f32, None));
+    }
     #[test]
     fn test_parse_amount_and_category() {
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78 dining").unwrap(),
-            (24.78f32, Some(Category::Dining))
+            (24.78f32, Some(Category::Dining), Details::default())
         );
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78 grocery").unwrap(),
-            (24.78f32, Some(Category::Grocery))
+            (24.78f32, Some(Category::Grocery), Details::default())
         );
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78 merchandise").unwrap(),
-            (24.78f32, Some(Category::Merchandise))
+            (24.78f32, Some(Category::Merchandise), Details::default())
         );
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78 travel").unwrap(),
-            (24.78f32, Some(Category::Travel))
+            (24.78f32, Some(Category::Travel), Details::default())
         );
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78 entertainment").unwrap(),
-            (24.78f32, Some(Category::Entertainment))
+            (24.78f32, Some(Category::Entertainment), Details::default())
         );
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78 other").unwrap(),
-            (24.78f32, Some(Category::Other))
+            (24.78f32, Some(Category::Other), Details::default())
         );
         assert_eq!(
             parse_amount_and_category(&mut "spent 24.78").unwrap(),
-            (24.78f32, None)
+            (24.78f32, None, Details::default())
+        );
+        assert_eq!(
+            parse_amount_and_category(&mut "spent 47 other at hardware store #house").unwrap(),
+            (
+                47f32,
+                Some(Category::Other),
+                Details {
+                    merchant: Some("hardware store".to_string()),
+                    tags: vec!["house".to_string()],
+                    note: None,
+                }
+            )
+        );
+        assert_eq!(
+            parse_amount_and_category(&mut "spent 12 #Lunch #work \"team outing\"").unwrap(),
+            (
+                12f32,
+                None,
+                Details {
+                    merchant: None,
+                    tags: vec!["lunch".to_string(), "work".to_string()],
+                    note: Some("team outing".to_string()),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_recurring_add() {
+        assert_eq!(
+            parse_recurring_add(&mut "recurring add 15.99 entertainment monthly 3").unwrap(),
+            (
+                15.99f32,
+                Some(Category::Entertainment),
+                Schedule::Monthly { day: 3 }
+            )
         );
+        assert_eq!(
+            parse_recurring_add(&mut "Recurring add 1200 every 2 weeks").unwrap(),
+            (1200f32, None, Schedule::Weekly { every: 2 })
+        );
+        assert_eq!(
+            parse_recurring_add(&mut "recurring add 30 other weekly").unwrap(),
+            (30f32, Some(Category::Other), Schedule::Weekly { every: 1 })
+        );
+        assert!(parse_recurring_add(&mut "recurring add 15.99 dining").is_err());
+    }
+
+    #[test]
+    fn test_parse_recurring_list_and_remove() {
+        assert!(is_recurring_list_request("recurring".to_string()));
+        assert!(is_recurring_list_request("recurring list".to_string()));
+        assert!(!is_recurring_list_request("recurring add 5".to_string()));
+        assert_eq!(
+            parse_recurring_remove(&mut "recurring remove 3").unwrap(),
+            3
+        );
+        assert!(parse_recurring_remove(&mut "recurring remove").is_err());
     }
 
     #[test]
@@ -232,11 +1109,36 @@ mod test {
         assert!(!is_spending_reset_request("other string".to_string()));
     }
 
+    #[test]
+    fn test_parse_spending_undo_request() {
+        assert!(parse_spending_undo_request(&mut "undo").is_ok());
+        assert!(parse_spending_undo_request(&mut "Spent Undo").is_ok());
+        assert!(parse_spending_undo_request(&mut "undo ").is_ok());
+        assert!(parse_spending_undo_request(&mut "spent 24.78").is_err());
+        assert!(parse_spending_undo_request(&mut "Undoubtedly the best pizza").is_err());
+        assert!(parse_spending_undo_request(&mut "undo that please").is_err());
+        assert!(is_spending_undo_request("undo".to_string()));
+        assert!(!is_spending_undo_request("other string".to_string()));
+    }
+
     #[test]
     fn test_parse_spending_total_request() {
         assert!(parse_spending_total_request(&mut "spent total").is_ok());
         assert!(parse_spending_total_request(&mut "other string").is_err());
         assert!(is_spending_total_request("spent total".to_string()));
         assert!(!is_spending_total_request("other string".to_string()));
+
+        assert_eq!(
+            parse_spending_total_query("spent total".to_string()),
+            Some(SpentQuery::default())
+        );
+        assert_eq!(
+            parse_spending_total_query("spent total at Costco #house".to_string()),
+            Some(SpentQuery {
+                tag: Some("house".to_string()),
+                merchant: Some("Costco".to_string()),
+                ..Default::default()
+            })
+        );
     }
 }
@@ -1,4 +1,20 @@
-use spending_tracker::{SpentRequest, SpentResponse, SpentTotalResponse};
+use spending_tracker::categories::Categories;
+use spending_tracker::export::ImportResponse;
+use spending_tracker::goals::{GoalProgress, SaveRequest};
+use spending_tracker::recurring::{Recurring, RecurringRequest, RecurringResponse};
+use spending_tracker::report::{Report, ReportPeriod, ReportQuery};
+use spending_tracker::{
+    LedgerInfo, LedgerRequest, SpentQuery, SpentRequest, SpentResponse, SpentTotalResponse,
+    UndoRequest, UndoResponse, CHAT_HEADER,
+};
+
+/// Whether a file sent to the bot looks like a statement the tracker can import.
+pub(crate) fn is_statement(file_name: &str) -> bool {
+    let file_name = file_name.to_lowercase();
+    [".ofx", ".qfx", ".qif"]
+        .iter()
+        .any(|extension| file_name.ends_with(extension))
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct SpendingAPI {
@@ -6,27 +22,48 @@ pub(crate) struct SpendingAPI {
     pub(crate) spending_reset_url: String,
     pub(crate) spending_add_url: String,
     pub(crate) budget_set_url: String,
+    pub(crate) undo_url: String,
+    pub(crate) ofx_import_url: String,
+    pub(crate) qif_import_url: String,
+    pub(crate) recurring_url: String,
+    pub(crate) report_url: String,
+    pub(crate) categories_url: String,
+    pub(crate) ledgers_url: String,
+    pub(crate) savings_url: String,
 }
 impl Default for SpendingAPI {
     fn default() -> Self {
-        let spending_base_url = "http://localhost:8001";
+        Self::new("http://localhost:8001")
+    }
+}
+
+impl SpendingAPI {
+    pub(crate) fn new(spending_base_url: &str) -> Self {
         Self {
             spending_add_url: format!("{spending_base_url}/spent"),
             spending_total_url: format!("{spending_base_url}/spent"),
             spending_reset_url: format!("{spending_base_url}/reset"),
             budget_set_url: format!("{spending_base_url}/budget"),
+            undo_url: format!("{spending_base_url}/undo"),
+            ofx_import_url: format!("{spending_base_url}/import/ofx"),
+            qif_import_url: format!("{spending_base_url}/import/qif"),
+            recurring_url: format!("{spending_base_url}/recurring"),
+            report_url: format!("{spending_base_url}/report"),
+            categories_url: format!("{spending_base_url}/categories"),
+            ledgers_url: format!("{spending_base_url}/ledgers"),
+            savings_url: format!("{spending_base_url}/savings"),
         }
     }
-}
 
-impl SpendingAPI {
     pub(crate) async fn spending_request(
         &self,
+        chat_id: i64,
         req: SpentRequest,
     ) -> Result<SpentResponse, reqwest::Error> {
         let client = reqwest::Client::new();
         let res = client
             .post(&self.spending_add_url)
+            .header(CHAT_HEADER, chat_id)
             .json(&req)
             .send()
             .await?
@@ -37,34 +74,209 @@ impl SpendingAPI {
 
     pub(crate) async fn spending_total_request(
         &self,
+        chat_id: i64,
+        query: SpentQuery,
     ) -> Result<SpentTotalResponse, reqwest::Error> {
-        let response: SpentTotalResponse = reqwest::get(&self.spending_total_url)
+        let client = reqwest::Client::new();
+        let response: SpentTotalResponse = client
+            .get(&self.spending_total_url)
+            .header(CHAT_HEADER, chat_id)
+            .query(&query)
+            .send()
             .await?
             .json::<SpentTotalResponse>()
             .await?;
         Ok(response)
     }
 
+    pub(crate) async fn report_request(
+        &self,
+        chat_id: i64,
+        period: ReportPeriod,
+    ) -> Result<Report, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let res = client
+            .get(&self.report_url)
+            .header(CHAT_HEADER, chat_id)
+            .query(&ReportQuery { period })
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    pub(crate) async fn categories_request(&self) -> Result<Categories, reqwest::Error> {
+        let response: Categories = reqwest::get(&self.categories_url)
+            .await?
+            .json::<Categories>()
+            .await?;
+        Ok(response)
+    }
+
     pub(crate) async fn spending_reset_request(
         &self,
+        chat_id: i64,
     ) -> Result<SpentTotalResponse, reqwest::Error> {
-        let response: SpentTotalResponse = reqwest::get(&self.spending_reset_url)
+        let client = reqwest::Client::new();
+        let response: SpentTotalResponse = client
+            .get(&self.spending_reset_url)
+            .header(CHAT_HEADER, chat_id)
+            .send()
             .await?
             .json::<SpentTotalResponse>()
             .await?;
         Ok(response)
     }
 
+    pub(crate) async fn undo_request(
+        &self,
+        chat_id: i64,
+        user_id: Option<i64>,
+    ) -> Result<UndoResponse, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let res = client
+            .post(&self.undo_url)
+            .header(CHAT_HEADER, chat_id)
+            .json(&UndoRequest {
+                chat_id: Some(chat_id),
+                user_id,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    /// Sends a bank statement file, picking the OFX or QIF importer from its file name.
+    pub(crate) async fn statement_import_request(
+        &self,
+        chat_id: i64,
+        file_name: &str,
+        statement: Vec<u8>,
+    ) -> Result<ImportResponse, reqwest::Error> {
+        let url = if file_name.to_lowercase().ends_with(".qif") {
+            &self.qif_import_url
+        } else {
+            &self.ofx_import_url
+        };
+        let client = reqwest::Client::new();
+        let res = client
+            .post(url)
+            .header(CHAT_HEADER, chat_id)
+            .body(statement)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    pub(crate) async fn recurring_add_request(
+        &self,
+        chat_id: i64,
+        req: RecurringRequest,
+    ) -> Result<Recurring, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let res = client
+            .post(&self.recurring_url)
+            .header(CHAT_HEADER, chat_id)
+            .json(&req)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    pub(crate) async fn recurring_list_request(
+        &self,
+        chat_id: i64,
+    ) -> Result<RecurringResponse, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let response: RecurringResponse = client
+            .get(&self.recurring_url)
+            .header(CHAT_HEADER, chat_id)
+            .send()
+            .await?
+            .json::<RecurringResponse>()
+            .await?;
+        Ok(response)
+    }
+
+    pub(crate) async fn recurring_remove_request(
+        &self,
+        chat_id: i64,
+        id: u64,
+    ) -> Result<RecurringResponse, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let res = client
+            .delete(format!("{}/{id}", self.recurring_url))
+            .header(CHAT_HEADER, chat_id)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
     pub(crate) async fn budget_set_request(
         &self,
+        chat_id: i64,
         req: SpentRequest,
     ) -> Result<SpentResponse, reqwest::Error> {
         let client = reqwest::Client::new();
         let res = client
             .post(&self.budget_set_url)
+            .header(CHAT_HEADER, chat_id)
+            .json(&req)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    pub(crate) async fn save_request(
+        &self,
+        chat_id: i64,
+        req: SaveRequest,
+    ) -> Result<GoalProgress, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let res = client
+            .post(&self.savings_url)
+            .header(CHAT_HEADER, chat_id)
             .json(&req)
             .send()
             .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    /// Points the chat at the named ledger, so everyone in it, or in any other chat pointed
+    /// at the same name, shares one budget.
+    pub(crate) async fn ledger_request(
+        &self,
+        chat_id: i64,
+        name: String,
+    ) -> Result<LedgerInfo, reqwest::Error> {
+        let client = reqwest::Client::new();
+        let res = client
+            .post(&self.ledgers_url)
+            .json(&LedgerRequest {
+                name,
+                chat_id: Some(chat_id),
+            })
+            .send()
+            .await?
+            .error_for_status()?
             .json()
             .await?;
         Ok(res)